@@ -0,0 +1,15 @@
+// Feeds arbitrary bytes to the OSM parser, which must never panic.
+//
+// Run from the repository root with a nightly toolchain:
+//
+//     cargo install cargo-fuzz
+//     cargo +nightly fuzz run read_from_reader
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use hello_rust::RoadNetwork;
+
+fuzz_target!(|data: &[u8]| {
+    let mut road_network = RoadNetwork::new();
+    let _ = road_network.read_from_reader(data);
+});
@@ -0,0 +1,185 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::{RoadNetwork, RoutingError};
+
+// Fastest speed (in m/s) any arc of the network is travelled at, so that
+// `distance / max_speed` never overestimates the remaining cost.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SpeedBound {
+    max_speed: f32,
+    // Arcs that got cost 0 despite a positive length (truncated costs of very
+    // short segments). No speed bound holds for them.
+    has_free_arcs: bool,
+}
+
+impl RoadNetwork {
+    fn _speed_bound(&self) -> SpeedBound {
+        *self.speed_bound.get_or_init(|| {
+            let mut bound = SpeedBound {max_speed: 0_f32, has_free_arcs: false};
+            for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+                let Some(from) = self.point_by_index(index) else { continue };
+                for arc in arcs {
+                    let Some(to) = self.point_by_index(arc.index) else { continue };
+                    let distance = self.distance_model.distance(from, to);
+                    if arc.cost > 0 {
+                        bound.max_speed = bound.max_speed.max(distance / arc.cost as f32);
+                    } else if distance > 0_f32 {
+                        bound.has_free_arcs = true;
+                    }
+                }
+            }
+            bound
+        })
+    }
+
+    // A* guided by the straight-line distance to `to`, with the heuristic
+    // inflated by `1 + epsilon`. `epsilon = 0` gives exact results; larger
+    // values settle fewer nodes (and answer faster), at the price of routes
+    // up to `1 + epsilon` times as expensive as the best one. The flag tells
+    // whether the route is provably the cheapest, which needs `epsilon = 0`
+    // and no zero-cost arcs of positive length (see `arc_cost_summary`).
+    pub fn shortest_path_astar(
+        &self, from: isize, to: isize, epsilon: f32
+    ) -> Option<(usize, Vec<isize>, bool)> {
+        self.shortest_path_astar_limited(from, to, epsilon, None).ok()
+    }
+
+    // Like `shortest_path_astar`, but fails with `RoutingError::LimitExceeded`
+    // once more than `max_settled_nodes` nodes were settled without reaching
+    // `to`, as `shortest_path_limited` does.
+    pub fn shortest_path_astar_limited(
+        &self, from: isize, to: isize, epsilon: f32, max_settled_nodes: Option<usize>
+    ) -> Result<(usize, Vec<isize>, bool), RoutingError> {
+        let source = self._index_of(from)?;
+        let target = self._index_of(to)?;
+        let bound = self._speed_bound();
+        let weight = 1_f32 + epsilon.max(0_f32);
+        let target_point = self.point_by_index(target);
+        let heuristic = |index: usize| -> usize {
+            match (self.point_by_index(index), target_point) {
+                (Some(point), Some(target_point)) if bound.max_speed > 0_f32 => {
+                    (weight * self.distance_model.distance(point, target_point) / bound.max_speed) as usize
+                }
+                _ => 0,
+            }
+        };
+
+        let mut costs = vec![usize::MAX; self.adjacent_arcs.len()];
+        let mut predecessors = vec![None; self.adjacent_arcs.len()];
+        let mut settled = vec![false; self.adjacent_arcs.len()];
+        let mut settled_count = 0;
+        let mut queue: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        costs[source] = 0;
+        queue.push(Reverse((heuristic(source), source)));
+
+        while let Some(Reverse((_, index))) = queue.pop() {
+            if settled[index] {
+                continue;
+            }
+            settled[index] = true;
+            if index == target {
+                break;
+            }
+            settled_count += 1;
+            if max_settled_nodes.is_some_and(|limit| settled_count > limit) {
+                return Err(RoutingError::LimitExceeded);
+            }
+            for arc in &self.adjacent_arcs[index] {
+                let new_cost = costs[index].saturating_add(arc.cost);
+                if new_cost < costs[arc.index] {
+                    costs[arc.index] = new_cost;
+                    predecessors[arc.index] = Some(index);
+                    queue.push(Reverse((new_cost.saturating_add(heuristic(arc.index)), arc.index)));
+                }
+            }
+        }
+        if costs[target] == usize::MAX {
+            return Err(RoutingError::NoRoute);
+        }
+        let mut path = vec![target];
+        while let Some(previous) = predecessors[path[path.len() - 1]] {
+            path.push(previous);
+        }
+        path.reverse();
+        let optimal = epsilon <= 0_f32 && !bound.has_free_arcs;
+        Ok((costs[target], self._to_osm_ids(path), optimal))
+    }
+
+    // For judging the A* heuristic: the true cost from `from` to `to` and
+    // the lower bound `shortest_path_astar` (with `epsilon = 0`) estimates
+    // for it from the straight-line distance. The crate has no landmarks, so
+    // this is the only bound there is. It is 0 when it can't be trusted
+    // (zero-cost arcs of positive length) or a node lacks coordinates.
+    // `None` if `to` isn't reachable.
+    pub fn shortest_path_landmark_lower_bound(&self, from: isize, to: isize) -> Option<(usize, usize)> {
+        let (cost, _) = self.shortest_path(from, to)?;
+        let bound = self._speed_bound();
+        let lower_bound = match (self.nodes.get(&from), self.nodes.get(&to)) {
+            (Some(&a), Some(&b)) if bound.max_speed > 0_f32 && !bound.has_free_arcs => {
+                (self.distance_model.distance(a, b) / bound.max_speed) as usize
+            }
+            _ => 0,
+        };
+        Some((cost, lower_bound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, RoutingError};
+
+    #[test]
+    fn settled_node_limit() {
+        let network = fixtures::grid(10);
+        assert_eq!(network.shortest_path_astar_limited(1, 100, 0_f32, Some(5)), Err(RoutingError::LimitExceeded));
+        let (cost, path, optimal) = network.shortest_path_astar_limited(1, 100, 0_f32, Some(1000)).unwrap();
+        // grids have many equally cheap paths, so only the cost has to match
+        assert_eq!(Some(cost), network.shortest_path(1, 100).map(|(cost, _)| cost));
+        assert_eq!((path[0], path[path.len() - 1], path.len()), (1, 100, 19));
+        assert!(optimal);
+    }
+
+    #[test]
+    fn inflated_heuristic_settles_fewer_nodes() {
+        let network = fixtures::grid(10);
+        let (cost, _) = network.shortest_path(1, 100).unwrap();
+        let (exact_cost, _, optimal) = network.shortest_path_astar(1, 100, 0_f32).unwrap();
+        assert_eq!((exact_cost, optimal), (cost, true));
+        // near-greedy search walks almost straight to the target
+        assert_eq!(network.shortest_path_astar_limited(1, 100, 0_f32, Some(30)), Err(RoutingError::LimitExceeded));
+        let (greedy_cost, path, optimal) = network.shortest_path_astar_limited(1, 100, 2_f32, Some(30)).unwrap();
+        assert!(!optimal);
+        assert_eq!(fixtures::path_cost(&network, &path), Some(greedy_cost));
+        assert_eq!((path[0], path[path.len() - 1]), (1, 100));
+        assert!(greedy_cost >= cost && greedy_cost as f32 <= 3_f32 * cost as f32);
+    }
+
+    #[test]
+    fn lower_bound_never_exceeds_the_cost() {
+        // a grid with a few fast diagonals, so the bound is loose elsewhere
+        let mut network = fixtures::grid(8);
+        for step in (1..7).step_by(2) {
+            let (tail, head) = (step * 8 + step + 1, (step + 1) * 8 + step + 2);
+            network.add_arc(tail as isize, head as isize, 30_f32);
+        }
+        let mut state: u32 = 7;
+        let mut random_node = move || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as isize % 64 + 1
+        };
+        for _ in 0..200 {
+            let (from, to) = (random_node(), random_node());
+            let (cost, lower_bound) = network.shortest_path_landmark_lower_bound(from, to).unwrap();
+            assert_eq!(Some(cost), network.shortest_path(from, to).map(|(cost, _)| cost));
+            assert!(lower_bound <= cost, "{} -> {}: {} > {}", from, to, lower_bound, cost);
+            assert_eq!(from == to, lower_bound == 0 && cost == 0);
+        }
+        assert_eq!(network.shortest_path_landmark_lower_bound(1, 65), None);
+        // zero-cost arcs of positive length make the bound useless
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        network.add_node(4, crate::Point {lat: 49.01, lon: 7.003});
+        network.add_arc(3, 4, f32::INFINITY);
+        assert_eq!(network.shortest_path_landmark_lower_bound(1, 3).map(|(_, bound)| bound), Some(0));
+    }
+}
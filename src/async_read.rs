@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+
+use tokio::sync::mpsc;
+
+use crate::{ParseProgress, RoadNetwork};
+
+impl RoadNetwork {
+    // Parses `filename` on tokio's blocking thread pool (so it must be called
+    // from within a runtime) and resolves to the finished network. Progress is
+    // sent to `progress` along the way; a dropped receiver doesn't stop the
+    // parse. Consumes `self` so a configured network can be passed in, e.g.
+    // `RoadNetwork::new().with_speed_profile(profile).read_from_osm_file_async(..)`.
+    pub async fn read_from_osm_file_async(
+        mut self, filename: &str, progress: mpsc::Sender<ParseProgress>
+    ) -> io::Result<RoadNetwork> {
+        let filename = filename.to_string();
+        let task = tokio::task::spawn_blocking(move || {
+            let file = File::open(&filename)?;
+            let total_bytes = file.metadata().ok().map(|metadata| metadata.len());
+            self.read_from_reader_with_progress(BufReader::new(file), |update| {
+                let _ = progress.blocking_send(ParseProgress {total_bytes, ..update});
+            })?;
+            Ok(self)
+        });
+        task.await.map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm");
+
+    #[test]
+    fn async_load_reports_progress() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let (network, updates) = runtime.block_on(async {
+            let (sender, mut receiver) = mpsc::channel(1);
+            let collector = tokio::spawn(async move {
+                let mut updates = Vec::new();
+                while let Some(update) = receiver.recv().await {
+                    updates.push(update);
+                }
+                updates
+            });
+            let network = RoadNetwork::new().read_from_osm_file_async(FIXTURE, sender).await.unwrap();
+            (network, collector.await.unwrap())
+        });
+        let mut expected = RoadNetwork::new();
+        expected.read_from_osm_file(FIXTURE).unwrap();
+        assert!(network.structurally_equal(&expected));
+        let last = updates.last().unwrap();
+        let total_bytes = std::fs::metadata(FIXTURE).unwrap().len();
+        assert_eq!((last.bytes_read, last.total_bytes, last.nodes, last.ways), (total_bytes, Some(total_bytes), 7, 4));
+    }
+
+    #[test]
+    fn async_load_of_missing_file_fails() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let (sender, _receiver) = mpsc::channel(1);
+        let result = runtime.block_on(RoadNetwork::new().read_from_osm_file_async("does/not/exist.osm", sender));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
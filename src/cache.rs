@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::RoadNetwork;
+
+fn to_io_error(error: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl RoadNetwork {
+    // Binary snapshot of the parsed network, much faster to load than the
+    // original OSM file. The distance model is not stored (loads as
+    // `Haversine`), which only matters for arcs added after loading.
+    pub fn write_cache<W: Write>(&self, writer: W) -> io::Result<()> {
+        bincode::serialize_into(writer, self).map_err(to_io_error)
+    }
+
+    pub fn read_cache<R: Read>(reader: R) -> io::Result<RoadNetwork> {
+        bincode::deserialize_from(reader).map_err(to_io_error)
+    }
+
+    pub fn write_cache_file(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write_cache(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_cache_file(filename: &str) -> io::Result<RoadNetwork> {
+        RoadNetwork::read_cache(BufReader::new(File::open(filename)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, RoadNetwork};
+
+    #[test]
+    fn cache_round_trip() {
+        let network = fixtures::grid(3);
+        let mut cache = Vec::new();
+        network.write_cache(&mut cache).unwrap();
+        let restored = RoadNetwork::read_cache(&cache[..]).unwrap();
+        assert!(restored.structurally_equal(&network));
+        assert_eq!(restored.nodes, network.nodes);
+        assert!(RoadNetwork::read_cache(&cache[..cache.len() / 2]).is_err());
+    }
+}
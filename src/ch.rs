@@ -0,0 +1,318 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::RoadNetwork;
+
+// Nodes a witness search may settle before giving up. A witness it misses
+// only costs an unnecessary shortcut, never a wrong query result.
+const WITNESS_SETTLED_LIMIT: usize = 500;
+
+// Preprocessed network for fast repeated queries. Nodes are contracted one
+// by one; shortcut arcs keep distances between the remaining nodes intact.
+// A query then only has to search upwards in the contraction order, from
+// both ends.
+#[derive(Debug)]
+pub struct ContractionHierarchy {
+    osm_id_map: HashMap<isize, usize>,
+    osm_ids: Vec<isize>,
+    up: Vec<Vec<(usize, usize)>>,  // (head, cost) of arcs towards later contracted nodes
+    down: Vec<Vec<(usize, usize)>>,  // (tail, cost) of arcs coming from later contracted nodes
+    middle: HashMap<(usize, usize), usize>,  // contracted node a shortcut skips
+    shortcut_count: usize,
+}
+
+struct Contraction {
+    outgoing: Vec<HashMap<usize, usize>>,
+    incoming: Vec<HashMap<usize, usize>>,
+    contracted: Vec<bool>,
+    contracted_neighbors: Vec<usize>,
+    witness_search: bool,
+}
+
+impl Contraction {
+    // Shortcuts needed to contract `node`: one per pair of (uncontracted)
+    // in- and out-neighbor, unless the witness search finds a path between
+    // them that avoids `node` and is at most as expensive.
+    fn shortcuts(&self, node: usize) -> Vec<(usize, usize, usize)> {
+        let mut shortcuts = Vec::new();
+        let max_out = self.outgoing[node].values().copied().max().unwrap_or(0);
+        for (&tail, &cost_in) in &self.incoming[node] {
+            let witnesses = if self.witness_search {
+                self.witness_costs(tail, node, cost_in.saturating_add(max_out), &self.outgoing[node])
+            } else {
+                HashMap::new()
+            };
+            for (&head, &cost_out) in &self.outgoing[node] {
+                let cost = cost_in.saturating_add(cost_out);
+                if tail != head && witnesses.get(&head).is_none_or(|&witness| witness > cost) {
+                    shortcuts.push((tail, head, cost));
+                }
+            }
+        }
+        shortcuts
+    }
+
+    // Dijkstra over the not yet contracted nodes from `source`, never
+    // passing `avoid` and stopping once all `targets` are settled, beyond
+    // `max_cost` or after `WITNESS_SETTLED_LIMIT` nodes.
+    fn witness_costs(
+        &self, source: usize, avoid: usize, max_cost: usize, targets: &HashMap<usize, usize>
+    ) -> HashMap<usize, usize> {
+        let mut costs = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        let mut settled = 0;
+        let mut open_targets = targets.len();
+        costs.insert(source, 0);
+        queue.push(Reverse((0, source)));
+        while let Some(Reverse((cost, node))) = queue.pop() {
+            if costs.get(&node).is_some_and(|&best| cost > best) {
+                continue;
+            }
+            settled += 1;
+            if cost > max_cost || settled > WITNESS_SETTLED_LIMIT {
+                break;
+            }
+            if targets.contains_key(&node) {
+                open_targets -= 1;
+                if open_targets == 0 {
+                    break;
+                }
+            }
+            for (&neighbor, &arc_cost) in &self.outgoing[node] {
+                let new_cost = arc_cost.saturating_add(cost);
+                if neighbor != avoid && costs.get(&neighbor).is_none_or(|&best| new_cost < best) {
+                    costs.insert(neighbor, new_cost);
+                    queue.push(Reverse((new_cost, neighbor)));
+                }
+            }
+        }
+        costs
+    }
+
+    // Edge difference plus contracted neighbors, so the order spreads out
+    // over the network instead of eating its way through one region.
+    fn priority(&self, node: usize) -> isize {
+        let shortcuts = self.shortcuts(node).len() as isize;
+        let removed = (self.incoming[node].len() + self.outgoing[node].len()) as isize;
+        shortcuts - removed + self.contracted_neighbors[node] as isize
+    }
+}
+
+impl RoadNetwork {
+    // Simple node ordering (edge difference, updated lazily) and no witness
+    // search: every in/out neighbor pair of a contracted node gets a shortcut
+    // unless a cheaper direct arc already exists. Fast to build on small
+    // networks, see `contracted_with_witness_search` for larger ones.
+    pub fn build_contraction_hierarchy(&self) -> ContractionHierarchy {
+        self._contract(false)
+    }
+
+    // Like `build_contraction_hierarchy`, but a bounded Dijkstra around each
+    // contracted node first looks for witness paths, and shortcuts are only
+    // added where none exists. Slower to build, with far fewer shortcuts and
+    // faster queries; the query results are the same.
+    pub fn contracted_with_witness_search(&self) -> ContractionHierarchy {
+        self._contract(true)
+    }
+
+    fn _contract(&self, witness_search: bool) -> ContractionHierarchy {
+        let node_count = self.adjacent_arcs.len();
+        let mut contraction = Contraction {
+            outgoing: vec![HashMap::new(); node_count],
+            incoming: vec![HashMap::new(); node_count],
+            contracted: vec![false; node_count],
+            contracted_neighbors: vec![0; node_count],
+            witness_search,
+        };
+        for (tail, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs.iter().filter(|arc| arc.index != tail) {
+                let cost = contraction.outgoing[tail].entry(arc.index).or_insert(arc.cost);
+                *cost = (*cost).min(arc.cost);
+                contraction.incoming[arc.index].insert(tail, *cost);
+            }
+        }
+
+        let mut hierarchy = ContractionHierarchy {
+            osm_id_map: self.osm_id_map.clone(),
+            osm_ids: self.osm_ids.clone(),
+            up: vec![Vec::new(); node_count],
+            down: vec![Vec::new(); node_count],
+            middle: HashMap::new(),
+            shortcut_count: 0,
+        };
+        let mut queue: BinaryHeap<Reverse<(isize, usize)>> = (0..node_count)
+            .map(|node| Reverse((contraction.priority(node), node)))
+            .collect();
+
+        while let Some(Reverse((priority, node))) = queue.pop() {
+            if contraction.contracted[node] {
+                continue;
+            }
+            // lazy update: re-queue if the priority got worse in the meantime
+            let current = contraction.priority(node);
+            if current > priority {
+                if let Some(&Reverse((next, _))) = queue.peek() {
+                    if current > next {
+                        queue.push(Reverse((current, node)));
+                        continue;
+                    }
+                }
+            }
+
+            for (tail, head, cost) in contraction.shortcuts(node) {
+                let existing = contraction.outgoing[tail].get(&head).copied();
+                if existing.is_none_or(|existing| cost < existing) {
+                    contraction.outgoing[tail].insert(head, cost);
+                    contraction.incoming[head].insert(tail, cost);
+                    hierarchy.middle.insert((tail, head), node);
+                    hierarchy.shortcut_count += 1;
+                }
+            }
+            contraction.contracted[node] = true;
+            let outgoing = std::mem::take(&mut contraction.outgoing[node]);
+            let incoming = std::mem::take(&mut contraction.incoming[node]);
+            for (&head, &cost) in &outgoing {
+                hierarchy.up[node].push((head, cost));
+                contraction.incoming[head].remove(&node);
+                contraction.contracted_neighbors[head] += 1;
+            }
+            for (&tail, &cost) in &incoming {
+                hierarchy.down[node].push((tail, cost));
+                contraction.outgoing[tail].remove(&node);
+                contraction.contracted_neighbors[tail] += 1;
+            }
+        }
+        hierarchy
+    }
+}
+
+impl ContractionHierarchy {
+    pub fn shortcut_count(&self) -> usize {
+        self.shortcut_count
+    }
+
+    // Same result as `RoadNetwork::shortest_path` (up to equally short
+    // alternatives), using a bidirectional upward search.
+    pub fn ch_query(&self, from: isize, to: isize) -> Option<(usize, Vec<isize>)> {
+        let source = *self.osm_id_map.get(&from)?;
+        let target = *self.osm_id_map.get(&to)?;
+        let (forward_costs, forward_predecessors) = self._upward_search(source, &self.up);
+        let (backward_costs, backward_predecessors) = self._upward_search(target, &self.down);
+
+        let mut best: Option<(usize, usize)> = None;  // (cost, meeting node)
+        for (node, &forward) in &forward_costs {
+            if let Some(&backward) = backward_costs.get(node) {
+                let cost = forward.saturating_add(backward);
+                if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                    best = Some((cost, *node));
+                }
+            }
+        }
+        let (cost, meeting) = best?;
+
+        let mut forward_nodes = vec![meeting];
+        while let Some(&previous) = forward_predecessors.get(forward_nodes.last()?) {
+            forward_nodes.push(previous);
+        }
+        forward_nodes.reverse();
+        let mut nodes = forward_nodes;
+        let mut current = meeting;
+        while let Some(&next) = backward_predecessors.get(&current) {
+            nodes.push(next);
+            current = next;
+        }
+
+        let mut path = vec![self.osm_ids[source]];
+        for pair in nodes.windows(2) {
+            self._unpack(pair[0], pair[1], &mut path);
+        }
+        Some((cost, path))
+    }
+
+    // Dijkstra restricted to the given upward arcs. The search space is small,
+    // so it uses hash maps instead of arrays over all nodes.
+    fn _upward_search(
+        &self, start: usize, arcs: &[Vec<(usize, usize)>]
+    ) -> (HashMap<usize, usize>, HashMap<usize, usize>) {
+        let mut costs = HashMap::new();
+        let mut predecessors = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        costs.insert(start, 0);
+        queue.push(Reverse((0, start)));
+        while let Some(Reverse((cost, node))) = queue.pop() {
+            if costs.get(&node).is_some_and(|&best| cost > best) {
+                continue;
+            }
+            for &(neighbor, arc_cost) in &arcs[node] {
+                let new_cost = arc_cost.saturating_add(cost);
+                if costs.get(&neighbor).is_none_or(|&best| new_cost < best) {
+                    costs.insert(neighbor, new_cost);
+                    predecessors.insert(neighbor, node);
+                    queue.push(Reverse((new_cost, neighbor)));
+                }
+            }
+        }
+        (costs, predecessors)
+    }
+
+    // Appends the original nodes of the arc `tail -> head` (without `tail`).
+    fn _unpack(&self, tail: usize, head: usize, path: &mut Vec<isize>) {
+        match self.middle.get(&(tail, head)) {
+            Some(&middle) => {
+                self._unpack(tail, middle, path);
+                self._unpack(middle, head, path);
+            }
+            None => path.push(self.osm_ids[head]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, RoadNetwork};
+
+    // 8 x 8 grid with a few one-way diagonals.
+    fn medium() -> RoadNetwork {
+        let mut network = fixtures::grid(8);
+        for (tail, head) in [(1, 10), (20, 11), (30, 39), (44, 35), (55, 64)] {
+            network.add_oneway_arc(tail, head, 10_f32);
+        }
+        network
+    }
+
+    fn assert_matches_dijkstra(network: &RoadNetwork, hierarchy: &super::ContractionHierarchy) {
+        for from in 1..=network.node_count() as isize {
+            for to in 1..=network.node_count() as isize {
+                let expected = network.shortest_path(from, to).map(|(cost, _)| cost);
+                let (cost, path) = hierarchy.ch_query(from, to).unwrap();
+                assert_eq!(Some(cost), expected, "{} -> {}", from, to);
+                assert_eq!((path[0], path[path.len() - 1]), (from, to));
+                assert_eq!(fixtures::path_cost(network, &path), Some(cost), "{} -> {}: {:?}", from, to, path);
+            }
+        }
+    }
+
+    #[test]
+    fn queries_match_dijkstra() {
+        let network = medium();
+        let hierarchy = network.build_contraction_hierarchy();
+        assert_matches_dijkstra(&network, &hierarchy);
+        assert_eq!(hierarchy.ch_query(1, 65), None);
+    }
+
+    #[test]
+    fn witnesses_avoid_redundant_shortcuts() {
+        // in a 3 x 3 grid, a way around every contracted node costs as much
+        // as the shortcut over it would
+        let grid = fixtures::grid(3);
+        assert_eq!(grid.build_contraction_hierarchy().shortcut_count(), 12);
+        let hierarchy = grid.contracted_with_witness_search();
+        assert_eq!(hierarchy.shortcut_count(), 0);
+        assert_matches_dijkstra(&grid, &hierarchy);
+
+        let network = medium();
+        let hierarchy = network.contracted_with_witness_search();
+        assert!(hierarchy.shortcut_count() < network.build_contraction_hierarchy().shortcut_count());
+        assert_matches_dijkstra(&network, &hierarchy);
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ParseProgress, RoadNetwork};
+
+// What `read_from_osm_file_resumable` saves. `file_size` guards against
+// resuming with a different input file.
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    file_size: u64,
+    progress: ParseProgress,
+    network: &'a RoadNetwork,
+}
+
+#[derive(Deserialize)]
+struct Checkpoint {
+    file_size: u64,
+    progress: ParseProgress,
+    network: RoadNetwork,
+}
+
+fn to_io_error(error: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl RoadNetwork {
+    // Like `read_from_osm_file`, but saves the partially built network to
+    // `checkpoint_filename` after every `interval_bytes` of input (at the next
+    // element boundary). If that file exists, e.g. because an earlier call
+    // crashed or was killed, parsing continues where the checkpoint left off
+    // instead of starting over; the finished network is the same. The
+    // checkpoint is removed once parsing succeeded. Checkpoints are written
+    // to a temporary file first, so an interrupted write keeps the previous one.
+    // Resuming keeps `self`'s distance model, everything else comes from the
+    // checkpoint.
+    pub fn read_from_osm_file_resumable(
+        &mut self, filename: &str, checkpoint_filename: &str, interval_bytes: u64
+    ) -> io::Result<()> {
+        let mut file = File::open(filename)?;
+        let file_size = file.metadata()?.len();
+        let mut progress = ParseProgress::default();
+        match File::open(checkpoint_filename) {
+            Ok(checkpoint) => {
+                let checkpoint: Checkpoint = bincode::deserialize_from(BufReader::new(checkpoint))
+                    .map_err(to_io_error)?;
+                if checkpoint.file_size != file_size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} was not written for {}", checkpoint_filename, filename),
+                    ));
+                }
+                let distance_model = self.distance_model.clone();
+                *self = checkpoint.network;
+                self.distance_model = distance_model;
+                progress = checkpoint.progress;
+                file.seek(SeekFrom::Start(progress.bytes_read))?;
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+        let temporary_filename = format!("{}.tmp", checkpoint_filename);
+        let mut last_checkpoint = progress.bytes_read;
+        self._read_from_reader(BufReader::new(file), None, progress, |_| {}, |network, progress| {
+            if progress.bytes_read < last_checkpoint.saturating_add(interval_bytes) {
+                return Ok(());
+            }
+            let mut writer = BufWriter::new(File::create(&temporary_filename)?);
+            let checkpoint = CheckpointRef {file_size, progress, network};
+            bincode::serialize_into(&mut writer, &checkpoint).map_err(to_io_error)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+            fs::rename(&temporary_filename, checkpoint_filename)?;
+            last_checkpoint = progress.bytes_read;
+            Ok(())
+        })?;
+        for leftover in [checkpoint_filename, temporary_filename.as_str()] {
+            match fs::remove_file(leftover) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, MissingCoordPolicy, RoadNetwork};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("hello-rust-checkpoint-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn resume_after_a_failed_parse() {
+        let nodes: Vec<(isize, f32, f32)> = (1..=6).map(|osm_id| (osm_id, 49_f32, 7_f32 + osm_id as f32 * 0.001)).collect();
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        // node 9 doesn't exist, which fails the parse under `MissingCoordPolicy::Error`
+        let broken = fixtures::osm_xml(&nodes, &[(10, &[1, 2, 3], residential), (11, &[3, 9], residential), (12, &[4, 5, 6], residential)]);
+        let fixed = broken.replace(r#"<nd ref="9"/>"#, r#"<nd ref="4"/>"#);
+        let (input, checkpoint) = (temp_path("input.osm"), temp_path("checkpoint.bin"));
+        std::fs::write(&input, &broken).unwrap();
+
+        let configured = || RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::Error);
+        let mut interrupted = configured();
+        assert!(interrupted.read_from_osm_file_resumable(&input, &checkpoint, 1).is_err());
+        assert!(std::path::Path::new(&checkpoint).exists());
+
+        // the checkpoint is only used for the file it was written for
+        std::fs::write(&input, format!("{} ", fixed)).unwrap();
+        let error = configured().read_from_osm_file_resumable(&input, &checkpoint, 1).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        // same size, so the checkpoint still matches
+        std::fs::write(&input, &fixed).unwrap();
+        let mut resumed = configured();
+        resumed.read_from_osm_file_resumable(&input, &checkpoint, 1).unwrap();
+        assert!(!std::path::Path::new(&checkpoint).exists());
+        std::fs::remove_file(&input).unwrap();
+
+        let uninterrupted = fixtures::parse_into(configured(), &fixed);
+        assert_eq!(uninterrupted.arc_count(), 10);
+        assert!(resumed.structurally_equal(&uninterrupted));
+        assert_eq!(resumed.parse_stats(), uninterrupted.parse_stats());
+    }
+}
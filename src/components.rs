@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+
+use crate::{Arc, RoadNetwork};
+
+impl RoadNetwork {
+    // Tarjan's algorithm (iterative, to survive long chains on real data).
+    // Each component lists internal indices; within a component every node
+    // can reach every other node following the arc directions.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let node_count = self.adjacent_arcs.len();
+        let mut order = vec![usize::MAX; node_count];
+        let mut lowlink = vec![0; node_count];
+        let mut on_stack = vec![false; node_count];
+        let mut stack = Vec::new();
+        let mut components = Vec::new();
+        let mut next_order = 0;
+
+        for root in 0..node_count {
+            if order[root] != usize::MAX {
+                continue;
+            }
+            // (node, position of the next arc to look at)
+            let mut call_stack = vec![(root, 0)];
+            order[root] = next_order;
+            lowlink[root] = next_order;
+            next_order += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&mut (node, ref mut next_arc)) = call_stack.last_mut() {
+                if let Some(arc) = self.adjacent_arcs[node].get(*next_arc) {
+                    *next_arc += 1;
+                    let successor = arc.index;
+                    if order[successor] == usize::MAX {
+                        order[successor] = next_order;
+                        lowlink[successor] = next_order;
+                        next_order += 1;
+                        stack.push(successor);
+                        on_stack[successor] = true;
+                        call_stack.push((successor, 0));
+                    } else if on_stack[successor] {
+                        lowlink[node] = lowlink[node].min(order[successor]);
+                    }
+                    continue;
+                }
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[node]);
+                }
+                if lowlink[node] == order[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    // All nodes reachable from `source` following the arc directions,
+    // including `source` itself; empty if it isn't part of the graph.
+    pub fn connected_to(&self, source: isize) -> HashSet<isize> {
+        let Some(start) = self.get_index(source) else { return HashSet::new() };
+        let mut reached = vec![false; self.adjacent_arcs.len()];
+        reached[start] = true;
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            for arc in &self.adjacent_arcs[index] {
+                if !reached[arc.index] {
+                    reached[arc.index] = true;
+                    stack.push(arc.index);
+                }
+            }
+        }
+        (0..reached.len()).filter(|&index| reached[index]).map(|index| self.osm_ids[index]).collect()
+    }
+
+    // Labels every node with its weakly connected component (ignoring arc
+    // directions) for `is_connected` and `component_sizes`. Adding nodes or
+    // arcs afterwards drops the labels, so call this again after changes.
+    pub fn compute_components(&mut self) {
+        let mut parents: Vec<usize> = (0..self.adjacent_arcs.len()).collect();
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                let (root_a, root_b) = (find(&mut parents, index), find(&mut parents, arc.index));
+                parents[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+        // number the components in the order of their lowest index
+        let mut ids = vec![usize::MAX; parents.len()];
+        let mut labels = Vec::with_capacity(parents.len());
+        let mut component_count = 0;
+        for index in 0..parents.len() {
+            let root = find(&mut parents, index);
+            if ids[root] == usize::MAX {
+                ids[root] = component_count;
+                component_count += 1;
+            }
+            labels.push(ids[root]);
+        }
+        self.component_labels = Some(labels);
+    }
+
+    // Whether `a` and `b` are in the same weakly connected component. `false`
+    // rules out a route between them, `true` doesn't promise one (one-way
+    // streets may still be in the way). `None` if either node isn't part of
+    // the graph or the components aren't computed (see `compute_components`).
+    pub fn is_connected(&self, a: isize, b: isize) -> Option<bool> {
+        let labels = self.component_labels.as_ref()?;
+        Some(labels[self.get_index(a)?] == labels[self.get_index(b)?])
+    }
+
+    // Number of nodes in each component, by component id (the lowest index
+    // comes first). Empty if the components aren't computed.
+    pub fn component_sizes(&self) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        for &label in self.component_labels.iter().flatten() {
+            if label == sizes.len() {
+                sizes.push(0);
+            }
+            sizes[label] += 1;
+        }
+        sizes
+    }
+
+    // Nodes with exactly one neighbor, ignoring arc directions: the ends of
+    // stubs and cul-de-sacs.
+    pub fn find_dead_ends(&self) -> Vec<isize> {
+        let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); self.adjacent_arcs.len()];
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs.iter().filter(|arc| arc.index != index) {
+                neighbors[index].insert(arc.index);
+                neighbors[arc.index].insert(index);
+            }
+        }
+        (0..neighbors.len()).filter(|&index| neighbors[index].len() == 1).map(|index| self.osm_ids[index]).collect()
+    }
+
+    // Nodes that can be entered but not left (e.g. the end of a one-way
+    // street). Nodes without any arcs count as neither sinks nor sources.
+    pub fn find_sinks(&self) -> Vec<isize> {
+        let incoming = self._has_incoming();
+        (0..self.adjacent_arcs.len())
+            .filter(|&index| incoming[index] && self.adjacent_arcs[index].is_empty())
+            .map(|index| self.osm_ids[index])
+            .collect()
+    }
+
+    // Nodes that can be left but not entered.
+    pub fn find_sources(&self) -> Vec<isize> {
+        let incoming = self._has_incoming();
+        (0..self.adjacent_arcs.len())
+            .filter(|&index| !incoming[index] && !self.adjacent_arcs[index].is_empty())
+            .map(|index| self.osm_ids[index])
+            .collect()
+    }
+
+    fn _has_incoming(&self) -> Vec<bool> {
+        let mut incoming = vec![false; self.adjacent_arcs.len()];
+        for arc in self.adjacent_arcs.iter().flatten() {
+            incoming[arc.index] = true;
+        }
+        incoming
+    }
+
+    // Copy of the network restricted to its largest strongly connected
+    // component, so any two of its nodes are routable in both directions.
+    pub fn largest_strongly_connected(&self) -> RoadNetwork {
+        let mut keep = vec![false; self.adjacent_arcs.len()];
+        if let Some(largest) = self.strongly_connected_components().iter().max_by_key(|c| c.len()) {
+            for &index in largest {
+                keep[index] = true;
+            }
+        }
+        self._subgraph(&keep)
+    }
+
+    // The transpose: the same nodes and indices, every arc `a -> b` turned
+    // into `b -> a` with the same cost. Searching it from a target gives the
+    // costs of reaching that target.
+    pub fn reverse(&self) -> RoadNetwork {
+        let mut reversed = self._empty_like();
+        reversed.osm_id_map = self.osm_id_map.clone();
+        reversed.osm_ids = self.osm_ids.clone();
+        reversed.nodes = self.nodes.clone();
+        reversed.points = self.points.clone();
+        reversed.barriers = self.barriers.clone();
+        reversed.parse_stats = self.parse_stats;
+        reversed.adjacent_arcs = vec![Vec::new(); self.adjacent_arcs.len()];
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                reversed.adjacent_arcs[arc.index].push(Arc {index, ..*arc});
+            }
+        }
+        reversed.conditional_speeds = self.conditional_speeds.clone();
+        reversed.dimension_limits = self.dimension_limits.clone();
+        reversed
+    }
+
+    // New network containing only the nodes with `keep[index]` set and the
+    // arcs between them.
+    pub(crate) fn _subgraph(&self, keep: &[bool]) -> RoadNetwork {
+        let mut subgraph = self._empty_like();
+        for (index, osm_id) in self.osm_ids.iter().enumerate() {
+            if keep[index] {
+                subgraph.get_or_create_index(*osm_id);
+                if let Some(&location) = self.nodes.get(osm_id) {
+                    subgraph.add_node(*osm_id, location);
+                }
+                if let Some(barrier) = self.barriers.get(osm_id) {
+                    subgraph.barriers.insert(*osm_id, barrier.clone());
+                }
+            }
+        }
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            if !keep[index] {
+                continue;
+            }
+            let new_index = subgraph.osm_id_map[&self.osm_ids[index]];
+            for arc in arcs.iter().filter(|arc| keep[arc.index]) {
+                let mut arc = *arc;
+                arc.index = subgraph.osm_id_map[&self.osm_ids[arc.index]];
+                subgraph.adjacent_arcs[new_index].push(arc);
+            }
+        }
+        subgraph.conditional_speeds = self.conditional_speeds.clone();
+        subgraph.dimension_limits = self.dimension_limits.clone();
+        subgraph
+    }
+}
+
+// Root of `index` in a union-find forest, halving the path on the way.
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{fixtures, RoadNetwork};
+
+    // A triangle 1-2-3 with a one-way street from 3 into the pair 4-5, which
+    // can be entered but not left.
+    fn trap() -> RoadNetwork {
+        fixtures::network(&[(1, 2, true), (2, 3, true), (3, 1, true), (3, 4, false), (4, 5, true)])
+    }
+
+    fn sorted_osm_ids(network: &RoadNetwork, indices: &[usize]) -> Vec<isize> {
+        let mut osm_ids: Vec<isize> = indices.iter().map(|&index| network.get_osm_id(index).unwrap()).collect();
+        osm_ids.sort_unstable();
+        osm_ids
+    }
+
+    #[test]
+    fn one_way_trap_splits_components() {
+        let network = trap();
+        let mut components: Vec<Vec<isize>> = network.strongly_connected_components().iter()
+            .map(|component| sorted_osm_ids(&network, component))
+            .collect();
+        components.sort();
+        assert_eq!(components, vec![vec![1, 2, 3], vec![4, 5]]);
+        let largest = network.largest_strongly_connected();
+        assert_eq!(largest.adjacent_arcs().len(), 3);
+        assert_eq!(largest.adjacent_arcs().iter().map(Vec::len).sum::<usize>(), 6);
+        assert_eq!(largest.get_index(4), None);
+    }
+
+    #[test]
+    fn connected_to_follows_arc_directions() {
+        let network = trap();
+        assert_eq!(network.connected_to(1), HashSet::from([1, 2, 3, 4, 5]));
+        assert_eq!(network.connected_to(4), HashSet::from([4, 5]));
+        assert_eq!(network.connected_to(6), HashSet::new());
+    }
+
+    #[test]
+    fn reverse_flips_a_directed_triangle() {
+        let network = fixtures::network(&[(1, 2, false), (2, 3, false), (3, 1, false)]);
+        let reversed = network.reverse();
+        assert_eq!((reversed.node_count(), reversed.arc_count()), (3, 3));
+        for (tail, head) in [(1, 2), (2, 3), (3, 1)] {
+            assert!(fixtures::has_arc(&reversed, head, tail) && !fixtures::has_arc(&reversed, tail, head));
+            assert_eq!(reversed.get_index(tail), network.get_index(tail));
+            assert_eq!(fixtures::path_cost(&reversed, &[head, tail]), fixtures::path_cost(&network, &[tail, head]));
+        }
+        assert_eq!(reversed.shortest_path(1, 3).map(|(_, path)| path), Some(vec![1, 3]));
+        // reversing twice gives the same arcs again
+        let network = trap();
+        let reversed = network.reverse().reverse();
+        let heads = |network: &RoadNetwork, tail: usize| {
+            let mut heads: Vec<usize> = network.adjacent_arcs()[tail].iter().map(|arc| arc.index).collect();
+            heads.sort_unstable();
+            heads
+        };
+        assert!((0..network.node_count()).all(|tail| heads(&reversed, tail) == heads(&network, tail)));
+        let bidirectional = fixtures::network(&[(1, 2, true), (2, 3, true), (3, 1, true)]);
+        let reversed = bidirectional.reverse();
+        for (from, to) in [(1, 2), (2, 1), (1, 3), (3, 2)] {
+            assert_eq!(reversed.shortest_path(from, to), bidirectional.shortest_path(from, to));
+        }
+    }
+
+    #[test]
+    fn stubs_sinks_and_sources() {
+        // a triangle with the stub 3-6 and the parallel arcs 6-7; the trap's
+        // one-way 3 -> 4 leads into the pair 4-5
+        let mut network = trap();
+        network.add_node(6, crate::Point {lat: 49_f32, lon: 7.006});
+        network.add_node(7, crate::Point {lat: 49_f32, lon: 7.007});
+        network.add_arc(3, 6, 10_f32);
+        network.add_arc(6, 7, 10_f32);
+        network.add_arc(6, 7, 20_f32);
+        let mut dead_ends = network.find_dead_ends();
+        dead_ends.sort_unstable();
+        assert_eq!(dead_ends, vec![5, 7]);
+        assert!(network.find_sinks().is_empty() && network.find_sources().is_empty());
+        let one_way = fixtures::network(&[(1, 2, false), (2, 3, false), (4, 2, false)]);
+        let mut sources = one_way.find_sources();
+        sources.sort_unstable();
+        assert_eq!((one_way.find_sinks(), sources), (vec![3], vec![1, 4]));
+        let mut dead_ends = one_way.find_dead_ends();
+        dead_ends.sort_unstable();
+        assert_eq!(dead_ends, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn bridging_two_islands() {
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true), (4, 5, true), (5, 6, false)]);
+        assert_eq!(network.is_connected(1, 4), None);
+        assert!(network.component_sizes().is_empty());
+        network.compute_components();
+        assert_eq!((network.is_connected(1, 3), network.is_connected(1, 4)), (Some(true), Some(false)));
+        // weakly connected: 6 can't be left, but is part of the island
+        assert_eq!(network.is_connected(6, 4), Some(true));
+        assert_eq!(network.is_connected(1, 8), None);
+        assert_eq!(network.component_sizes(), vec![3, 3]);
+        // a bridge drops the labels until they are computed again
+        network.add_oneway_arc(3, 4, 10_f32);
+        assert_eq!(network.is_connected(1, 4), None);
+        network.compute_components();
+        assert_eq!(network.is_connected(1, 4), Some(true));
+        assert_eq!(network.is_connected(4, 1), Some(true));
+        assert_eq!(network.component_sizes(), vec![6]);
+        network.get_or_create_index(8);
+        assert_eq!(network.is_connected(1, 8), None);
+        network.compute_components();
+        assert_eq!((network.is_connected(1, 8), network.component_sizes()), (Some(false), vec![6, 1]));
+    }
+}
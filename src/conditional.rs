@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{arc_cost, Arc, RoadNetwork, SpeedProfile, KMPH};
+
+const DAY: u32 = 24 * 60 * 60;
+
+// Speed of a way's arcs while one of the time windows applies. Windows are
+// seconds since midnight, `(start, end)` with `end` exclusive; `start > end`
+// wraps around midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ConditionalSpeed {
+    pub speed_factor: f32,  // in m/s, penalties included like for the arcs
+    pub windows: Vec<(u32, u32)>,
+}
+
+impl ConditionalSpeed {
+    fn applies_at(&self, time_of_day: u32) -> bool {
+        self.windows.iter().any(|&(start, end)| if start <= end {
+            start <= time_of_day && time_of_day < end
+        } else {
+            start <= time_of_day || time_of_day < end
+        })
+    }
+}
+
+// One parsed condition: `kmh` during any of the windows (as in `ConditionalSpeed`).
+pub(crate) struct SpeedCondition {
+    kmh: f32,
+    windows: Vec<(u32, u32)>,
+}
+
+// Parses the basic form of `maxspeed:conditional`: "30 @ (22:00-06:00)",
+// several time ranges separated by commas and several conditions by
+// semicolons. `None` if any part is not understood (weekdays, weather
+// conditions, ...).
+pub(crate) fn parse_maxspeed_conditional(value: &str, profile: &SpeedProfile) -> Option<Vec<SpeedCondition>> {
+    value.split(';').map(|condition| {
+        let (speed, ranges) = condition.split_once('@')?;
+        let kmh = profile.maxspeed_kmh(speed)?;
+        let ranges = ranges.trim();
+        let ranges = ranges.strip_prefix('(').and_then(|ranges| ranges.strip_suffix(')')).unwrap_or(ranges);
+        let windows = ranges.split(',').map(|range| {
+            let (start, end) = range.split_once('-')?;
+            Some((parse_time(start)?, parse_time(end)?))
+        }).collect::<Option<Vec<_>>>()?;
+        Some(SpeedCondition {kmh, windows})
+    }).collect()
+}
+
+// "HH:MM" to seconds since midnight, "24:00" included.
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+    if hours > 24 || minutes > 59 || (hours == 24 && minutes > 0) {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60)
+}
+
+impl RoadNetwork {
+    pub(crate) fn _conditional_speeds(&self, penalty: f32, conditions: &[SpeedCondition]) -> Vec<ConditionalSpeed> {
+        conditions.iter().map(|condition| ConditionalSpeed {
+            speed_factor: KMPH * self.profile.capped_kmh(condition.kmh) / penalty,
+            windows: condition.windows.clone(),
+        }).collect()
+    }
+
+    // Cost of `arc` when entered at `time_of_day`: its length at the first
+    // matching speed of its way, rounded like when building arcs, otherwise
+    // `arc.cost`.
+    fn _cost_at(&self, arc: &Arc, time_of_day: u32) -> usize {
+        let conditional = arc.way
+            .and_then(|way| self.conditional_speeds.get(&way))
+            .and_then(|speeds| speeds.iter().find(|conditional| conditional.applies_at(time_of_day)));
+        match (conditional, arc.length) {
+            (Some(conditional), Some(length)) => arc_cost(length, conditional.speed_factor),
+            _ => arc.cost,
+        }
+    }
+
+    // Like `shortest_path`, but honours `maxspeed:conditional` time windows,
+    // `departure` being seconds since midnight. Each arc is costed by the
+    // time it is entered; waiting for a faster window is never considered.
+    // Conditions are looked up by the arc's way, so parallel ways between the
+    // same two nodes keep their own.
+    pub fn shortest_path_departing_at(
+        &self, from: isize, to: isize, departure: u32
+    ) -> Option<(usize, Vec<isize>)> {
+        let source = self.get_index(from)?;
+        let target = self.get_index(to)?;
+        let search = self._dijkstra(source, Some(target), None, None, |_, elapsed, arc| {
+            let time_of_day = ((departure as usize + elapsed) % DAY as usize) as u32;
+            Some(self._cost_at(arc, time_of_day))
+        }).ok()?;
+        let path = search.path_to(target)?;
+        Some((search.costs[target], self._to_osm_ids(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    const NODES: [(isize, f32, f32); 2] = [(1, 49_f32, 7_f32), (2, 49_f32, 7.01)];
+
+    fn road(conditional: &str) -> RoadNetwork {
+        let tags = [("highway", "residential"), ("maxspeed", "50"), ("maxspeed:conditional", conditional)];
+        fixtures::parse(&fixtures::osm_xml(&NODES, &[(10, &[1, 2], &tags)]))
+    }
+
+    #[test]
+    fn parse_time_windows() {
+        let profile = SpeedProfile::default();
+        let conditions = parse_maxspeed_conditional("30 @ (22:00-06:00); 70 @ 10:00-11:00,14:30-15:00", &profile).unwrap();
+        assert_eq!(conditions.iter().map(|condition| condition.kmh).collect::<Vec<_>>(), vec![30_f32, 70_f32]);
+        assert_eq!(conditions[0].windows, vec![(22 * 3600, 6 * 3600)]);
+        assert_eq!(conditions[1].windows, vec![(10 * 3600, 11 * 3600), (14 * 3600 + 1800, 15 * 3600)]);
+        assert!(parse_maxspeed_conditional("30 @ (Mo-Fr 07:00-09:00)", &profile).is_none());
+        assert!(parse_maxspeed_conditional("30 @ wet", &profile).is_none());
+        assert!(parse_maxspeed_conditional("30 @ (25:00-06:00)", &profile).is_none());
+    }
+
+    #[test]
+    fn conditional_speed_applies_inside_the_window() {
+        let network = road("30 @ (22:00-06:00)");
+        let (day, path) = network.shortest_path_departing_at(1, 2, 12 * 3600).unwrap();
+        assert_eq!(Some((day, path)), network.shortest_path(1, 2));
+        let (night, _) = network.shortest_path_departing_at(1, 2, 23 * 3600).unwrap();
+        let (early, _) = network.shortest_path_departing_at(1, 2, 5 * 3600).unwrap();
+        assert_eq!(night, early);
+        // 730 m at 30 instead of 50 km/h
+        assert!(night > day && (night as f32 / day as f32 - 5_f32 / 3_f32).abs() < 0.05, "{} {}", night, day);
+        assert_eq!(network.parse_stats().unparsed_conditions, 0);
+    }
+
+    #[test]
+    fn parallel_ways_keep_their_own_conditions() {
+        // only the residential street is slow at night, the motorway next to it isn't
+        let network = fixtures::parse(&fixtures::osm_xml(&NODES, &[
+            (10, &[1, 2], &[("highway", "residential"), ("maxspeed:conditional", "10 @ (22:00-06:00)")]),
+            (11, &[1, 2], &[("highway", "motorway"), ("maxspeed", "120")]),
+        ]));
+        let day = network.shortest_path_departing_at(1, 2, 12 * 3600);
+        assert_eq!(day, Some((21, vec![1, 2])));
+        assert_eq!(network.shortest_path_departing_at(1, 2, 23 * 3600), day);
+        assert_eq!(network.way_ids_along(&[1, 2]), vec![11]);
+    }
+
+    #[test]
+    fn unparsed_conditions_are_counted_and_ignored() {
+        let network = road("30 @ (Mo-Fr 22:00-06:00)");
+        assert_eq!(network.parse_stats().unparsed_conditions, 1);
+        assert_eq!(network.shortest_path_departing_at(1, 2, 23 * 3600), network.shortest_path(1, 2));
+    }
+
+    #[test]
+    fn conditional_costs_round_like_arcs() {
+        // two nodes mapped onto each other: never free, at any time
+        let tags = [("highway", "residential"), ("maxspeed:conditional", "30 @ (22:00-06:00)")];
+        let network = fixtures::parse(&fixtures::osm_xml(&[(1, 49_f32, 7_f32), (2, 49_f32, 7_f32)], &[(10, &[1, 2], &tags)]));
+        assert_eq!(network.parse_stats().zero_length_arcs, 1);
+        assert_eq!(network.shortest_path_departing_at(1, 2, 23 * 3600).map(|(cost, _)| cost), Some(1));
+        assert_eq!(network.shortest_path_departing_at(2, 1, 12 * 3600).map(|(cost, _)| cost), Some(1));
+        let network = road("30 @ (22:00-06:00)");
+        let (night, _) = network.shortest_path_departing_at(1, 2, 23 * 3600).unwrap();
+        assert_eq!(Some(night), network.arc_cost_at_speed(1, 2, 30_f32));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Debug;
+
+use crate::Point;
+
+const EARTH_RADIUS: f32 = 6_371_000_f32;  // mean radius in meters
+
+// How arc lengths are computed from node coordinates.
+pub trait DistanceModel: Debug + Send + Sync {
+    fn distance(&self, a: Point, b: Point) -> f32;
+
+    // South-west and north-east corner of a box containing every point
+    // within `distance` of `center` (`min.lon > max.lon` wrapping around the
+    // antimeridian), so nearest-node lookups only look at the nodes in it.
+    // Without one, they check every node.
+    fn search_box(&self, _center: Point, _distance: f32) -> Option<(Point, Point)> {
+        None
+    }
+}
+
+// Some slack for rounding, in degrees (about a meter on earth).
+const BOX_SLACK: f32 = 1e-5;
+
+// `center` widened by `dlat` and `dlon` degrees, clamped to the poles.
+// `None` for centers that aren't valid coordinates.
+fn degree_box(center: Point, dlat: f32, dlon: f32) -> Option<(Point, Point)> {
+    if !(center.lat.abs() <= 90_f32 && center.lon.abs() <= 180_f32) {
+        return None;
+    }
+    let (dlat, dlon) = (dlat + BOX_SLACK, dlon + BOX_SLACK);
+    let min = Point {lat: (center.lat - dlat).max(-90_f32), lon: center.lon - dlon};
+    let max = Point {lat: (center.lat + dlat).min(90_f32), lon: center.lon + dlon};
+    Some((min, max))
+}
+
+// Great-circle distance on a sphere, the default.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Haversine {
+    pub radius: f32,
+}
+
+// Flat approximation around the mean latitude of both points, cheaper than
+// `Haversine` and accurate for the short distances between OSM nodes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Equirectangular {
+    pub radius: f32,
+}
+
+// Treats `lat`/`lon` as plain planar coordinates (game maps, projected data).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Euclidean;
+
+impl Default for Haversine {
+    fn default() -> Haversine {
+        Haversine {radius: EARTH_RADIUS}
+    }
+}
+
+impl Default for Equirectangular {
+    fn default() -> Equirectangular {
+        Equirectangular {radius: EARTH_RADIUS}
+    }
+}
+
+impl DistanceModel for Haversine {
+    fn distance(&self, a: Point, b: Point) -> f32 {
+        let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+        let half_delta_lat = (lat_b - lat_a) / 2_f32;
+        let half_delta_lon = (b.lon - a.lon).to_radians() / 2_f32;
+        let h = half_delta_lat.sin().powi(2) + lat_a.cos() * lat_b.cos() * half_delta_lon.sin().powi(2);
+        2_f32 * self.radius * h.sqrt().min(1_f32).asin()
+    }
+
+    // Longitudes are compared around the globe, so the box may wrap.
+    fn search_box(&self, center: Point, distance: f32) -> Option<(Point, Point)> {
+        let angle = distance * 1.001 / self.radius;
+        let dlat = angle.to_degrees();
+        // the widest parallel within reach, unless a pole is
+        let sin_dlon = angle.sin() / center.lat.to_radians().cos();
+        let everywhere = angle >= std::f32::consts::FRAC_PI_2 || center.lat.abs() + dlat >= 90_f32 || sin_dlon >= 1_f32;
+        let dlon = if everywhere { 180_f32 } else { sin_dlon.asin().to_degrees() };
+        let (mut min, mut max) = degree_box(center, dlat, dlon)?;
+        if max.lon - min.lon >= 360_f32 {
+            (min.lon, max.lon) = (-180_f32, 180_f32);
+        } else if min.lon < -180_f32 {
+            min.lon += 360_f32;
+        } else if max.lon > 180_f32 {
+            max.lon -= 360_f32;
+        }
+        Some((min, max))
+    }
+}
+
+impl DistanceModel for Equirectangular {
+    fn distance(&self, a: Point, b: Point) -> f32 {
+        let mean_lat = ((a.lat + b.lat) / 2_f32).to_radians();
+        let x = (b.lon - a.lon).to_radians() * mean_lat.cos();
+        let y = (b.lat - a.lat).to_radians();
+        self.radius * (x * x + y * y).sqrt()
+    }
+
+    // Longitude differences aren't wrapped, so neither is the box.
+    fn search_box(&self, center: Point, distance: f32) -> Option<(Point, Point)> {
+        let dlat = (distance * 1.001 / self.radius).to_degrees();
+        // the mean latitude used for `x` lies at most `dlat / 2` farther out
+        let widest = (center.lat.abs() + dlat / 2_f32).to_radians().cos();
+        let dlon = if widest > 0_f32 { dlat / widest } else { 360_f32 };
+        degree_box(center, dlat, dlon)
+    }
+}
+
+impl DistanceModel for Euclidean {
+    fn distance(&self, a: Point, b: Point) -> f32 {
+        ((b.lat - a.lat).powi(2) + (b.lon - a.lon).powi(2)).sqrt()
+    }
+
+    fn search_box(&self, center: Point, distance: f32) -> Option<(Point, Point)> {
+        let distance = distance * 1.001 + BOX_SLACK;
+        Some((
+            Point {lat: center.lat - distance, lon: center.lon - distance},
+            Point {lat: center.lat + distance, lon: center.lon + distance},
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoadNetwork;
+
+    #[test]
+    fn euclidean_uses_plain_coordinates() {
+        let (a, b) = (Point {lat: 1_f32, lon: 2_f32}, Point {lat: 4_f32, lon: 6_f32});
+        assert_eq!(Euclidean.distance(a, b), 5_f32);
+        let mut network = RoadNetwork::new().with_distance_model(Euclidean);
+        network.add_nodes([(1, a), (2, b)]);
+        network.add_arc(1, 2, 1_f32);
+        assert_eq!(network.distance(1, 2), 5_f32);
+        assert_eq!(network.shortest_path(1, 2), Some((5, vec![1, 2])));
+    }
+
+    #[test]
+    fn radius_scales_distances() {
+        let (a, b) = (Point {lat: 0_f32, lon: 0_f32}, Point {lat: 0_f32, lon: 1_f32});
+        let earth = Haversine::default().distance(a, b);
+        assert!((earth - 111_195_f32).abs() < 10_f32);
+        let mars = Haversine {radius: 3_389_500_f32}.distance(a, b);
+        assert!((mars / earth - 3_389_500_f32 / EARTH_RADIUS).abs() < 1e-4);
+        assert!((Equirectangular::default().distance(a, b) - earth).abs() < 1_f32);
+    }
+}
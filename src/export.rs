@@ -0,0 +1,502 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::{Arc, RoadNetwork};
+
+// An arc as `(tail, head, arc)` by index.
+type Segment<'a> = (usize, usize, &'a Arc);
+
+// Speeds (in m/s) at which a stretch of arcs gets its costs back when
+// parsed again: above `min` and at most `max`.
+#[derive(Debug, Copy, Clone)]
+struct SpeedRange {
+    min: f32,
+    max: f32,
+}
+
+impl SpeedRange {
+    const ANY: SpeedRange = SpeedRange {min: 0_f32, max: f32::INFINITY};
+    const NONE: SpeedRange = SpeedRange {min: 0_f32, max: 0_f32};
+
+    // Narrowed to the speeds at which `meters` truncate to `cost` seconds;
+    // `None` if that leaves (almost) nothing. Arcs of unknown or zero length
+    // get their cost regardless of speed.
+    fn with_arc(self, meters: Option<f32>, cost: usize) -> Option<SpeedRange> {
+        let Some(meters) = meters.filter(|&meters| meters > 0_f32) else { return Some(self) };
+        let min = self.min.max(meters / (cost + 1) as f32);
+        let max = if cost == 0 { self.max } else { self.max.min(meters / cost as f32) };
+        // keep clear of rounding when the speed is written and parsed again
+        Some(SpeedRange {min, max}).filter(|range| range.max > range.min * 1.0001_f32)
+    }
+
+    fn speed(self) -> f32 {
+        if self.max.is_finite() { (self.min + self.max) / 2_f32 } else { self.min * 2_f32 }
+    }
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+// A CSV field, quoted (with quotes doubled) if it contains a separator,
+// quote or line break.
+fn escape_csv(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+impl RoadNetwork {
+    // One line per node with its neighbors and arc costs, using OSM IDs:
+    // `<osm_id>: <neighbor> (<cost>s), <neighbor> (<cost>s), ...`
+    pub fn write_adjacency<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            write!(writer, "{}:", self.osm_ids[index])?;
+            for (position, arc) in arcs.iter().enumerate() {
+                let separator = if position == 0 { " " } else { ", " };
+                write!(writer, "{}{} ({}s)", separator, self.osm_ids[arc.index], arc.cost)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    // Writes the graph as OSM XML that `read_from_reader` can load again, see
+    // `write_osm_xml`.
+    pub fn write_osm<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_osm_xml(writer)
+    }
+
+    // Writes all parsed nodes and the arcs as OSM XML again, e.g. to open a
+    // clipped or filtered network in JOSM. The arcs of each way are joined
+    // back into `<way>` elements with the original ID, tagged with the
+    // highway class (`road` for arcs added through `add_arc`), name and
+    // one-way direction. Where a way can't be written as one element (its
+    // parts were clipped apart, or differ in direction or speed), the
+    // remaining parts get negative IDs. A `maxspeed` tag (for ferries a
+    // `duration`) is chosen so that reading the file with the same profile
+    // gives the arcs their costs back; other tags are not kept.
+    pub fn write_osm_xml<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<osm version="0.6" generator="hello-rust">"#)?;
+        let nodes: BTreeMap<isize, _> = self.nodes.iter().map(|(&osm_id, &point)| (osm_id, point)).collect();
+        for (osm_id, point) in nodes {
+            match self.barriers.get(&osm_id) {
+                Some(barrier) => {
+                    writeln!(writer, r#" <node id="{}" lat="{}" lon="{}">"#, osm_id, point.lat, point.lon)?;
+                    writeln!(writer, r#"  <tag k="barrier" v="{}"/>"#, escape_xml(barrier))?;
+                    writeln!(writer, " </node>")?;
+                }
+                None => writeln!(writer, r#" <node id="{}" lat="{}" lon="{}"/>"#, osm_id, point.lat, point.lon)?,
+            }
+        }
+
+        // (way, bidirectional) -> (tail, head, arc) with tail < head for bidirectional arcs
+        let mut groups: BTreeMap<(Option<isize>, bool), Vec<Segment>> = BTreeMap::new();
+        for (tail, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                let bidirectional = self.adjacent_arcs[arc.index].iter()
+                    .any(|back| back.index == tail && (back.cost, back.way) == (arc.cost, arc.way));
+                if !bidirectional || tail < arc.index {
+                    groups.entry((arc.way, bidirectional)).or_default().push((tail, arc.index, arc));
+                }
+            }
+        }
+        // new IDs count down from below every existing one
+        let mut next_id = groups.keys().filter_map(|(way, _)| *way).min().unwrap_or(0).min(0);
+        let mut used_ids = HashSet::new();
+        for ((way, bidirectional), segments) in groups {
+            for (chain, speed, first) in self._chains(&segments, bidirectional) {
+                let id = match way.filter(|way| used_ids.insert(*way)) {
+                    Some(way) => way,
+                    None => {
+                        next_id -= 1;
+                        next_id
+                    }
+                };
+                writeln!(writer, r#" <way id="{}">"#, id)?;
+                for index in &chain {
+                    writeln!(writer, r#"  <nd ref="{}"/>"#, self.osm_ids[*index])?;
+                }
+                match self.arc_class(first).unwrap_or("road") {
+                    "ferry" => {
+                        writeln!(writer, r#"  <tag k="route" v="ferry"/>"#)?;
+                        let meters: f32 = chain.windows(2)
+                            .filter_map(|pair| Some(self.distance_model.distance(
+                                self.point_by_index(pair[0])?, self.point_by_index(pair[1])?
+                            )))
+                            .sum();
+                        if let Some(speed) = speed.filter(|_| meters > 0_f32) {
+                            let seconds = (meters / speed).round() as u64;
+                            let duration = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+                            writeln!(writer, r#"  <tag k="duration" v="{}"/>"#, duration)?;
+                        }
+                    }
+                    class => {
+                        writeln!(writer, r#"  <tag k="highway" v="{}"/>"#, escape_xml(class))?;
+                        if let Some(speed) = speed {
+                            writeln!(writer, r#"  <tag k="maxspeed" v="{}"/>"#, speed * 3.6_f32)?;
+                        }
+                    }
+                }
+                if let Some(name) = self.arc_name(first) {
+                    writeln!(writer, r#"  <tag k="name" v="{}"/>"#, escape_xml(name))?;
+                }
+                if !bidirectional {
+                    writeln!(writer, r#"  <tag k="oneway" v="yes"/>"#)?;
+                }
+                writeln!(writer, " </way>")?;
+            }
+        }
+        writeln!(writer, "</osm>")
+    }
+
+    // Joins segments `(tail, head, arc)` of one way into chains of indices,
+    // each with a speed reproducing the costs of all its arcs (`None` if any
+    // speed does) and its first arc. Bidirectional segments may be walked in
+    // either direction.
+    fn _chains<'a>(
+        &self, segments: &[Segment<'a>], bidirectional: bool
+    ) -> Vec<(Vec<usize>, Option<f32>, &'a Arc)> {
+        let mut incident: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut balance: HashMap<usize, isize> = HashMap::new();  // out minus in, or degree
+        for (position, &(tail, head, _)) in segments.iter().enumerate() {
+            incident.entry(tail).or_default().push(position);
+            *balance.entry(tail).or_default() += 1;
+            let head_incident = incident.entry(head).or_default();
+            if bidirectional {
+                head_incident.push(position);
+                *balance.entry(head).or_default() += 1;
+            } else {
+                *balance.entry(head).or_default() -= 1;
+            }
+        }
+        // chains preferably start where a way ends
+        let mut starts: Vec<usize> = incident.keys().copied().collect();
+        starts.sort_unstable_by_key(|start| {
+            let balance = balance[start];
+            let is_end = if bidirectional { balance % 2 == 1 } else { balance > 0 };
+            (!is_end, *start)
+        });
+
+        let mut used = vec![false; segments.len()];
+        let mut chains = Vec::new();
+        for start in starts {
+            loop {
+                let (mut chain, mut range, mut current) = (vec![start], SpeedRange::ANY, start);
+                let mut first = None;
+                while let Some((position, next, narrowed)) = incident[&current].iter()
+                    .filter(|&&position| !used[position])
+                    .find_map(|&position| {
+                        let (tail, head, arc) = segments[position];
+                        let next = if tail == current { head } else { tail };
+                        let meters = self.point_by_index(tail).zip(self.point_by_index(head))
+                            .map(|(a, b)| self.distance_model.distance(a, b));
+                        // an arc no speed fits (rounding) becomes a way of its own
+                        let narrowed = match range.with_arc(meters, arc.cost) {
+                            Some(narrowed) => narrowed,
+                            None if first.is_none() => SpeedRange::NONE,
+                            None => return None,
+                        };
+                        Some((position, next, narrowed))
+                    })
+                {
+                    used[position] = true;
+                    first.get_or_insert(segments[position].2);
+                    chain.push(next);
+                    range = narrowed;
+                    current = next;
+                }
+                let Some(first) = first else { break };
+                let speed = Some(range).filter(|range| range.min > 0_f32).map(SpeedRange::speed);
+                chains.push((chain, speed, first));
+            }
+        }
+        chains
+    }
+
+    // GeoJSON FeatureCollection with one LineString per arc.
+    pub fn write_geojson<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, r#"{{"type":"FeatureCollection","features":["#)?;
+        let mut first = true;
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            let osm_id = self.osm_ids[index];
+            let Some(from) = self.nodes.get(&osm_id) else { continue };
+            for arc in arcs {
+                let neighbor = self.osm_ids[arc.index];
+                let Some(to) = self.nodes.get(&neighbor) else { continue };
+                if !first {
+                    write!(writer, ",")?;
+                }
+                first = false;
+                write!(
+                    writer,
+                    concat!(
+                        "\n", r#"{{"type":"Feature","geometry":{{"type":"LineString","#,
+                        r#""coordinates":[[{},{}],[{},{}]]}},"#,
+                        r#""properties":{{"from":{},"to":{},"cost":{},"name":{},"way":{}}}}}"#
+                    ),
+                    from.lon, from.lat, to.lon, to.lat, osm_id, neighbor, arc.cost,
+                    serde_json::to_string(&self.arc_name(arc))?, serde_json::to_string(&arc.way)?
+                )?;
+            }
+        }
+        writeln!(writer, "\n]}}")
+    }
+
+    // Edge list with a header and one row per arc:
+    // `from,to,cost,way_id,class,name` with OSM IDs and the cost in seconds.
+    // The way ID, class and name are empty if unknown; class and name are
+    // quoted as CSV needs it (RFC 4180), in UTF-8.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "from,to,cost,way_id,class,name")?;
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                writeln!(
+                    writer, "{},{},{},{},{},{}",
+                    self.osm_ids[index], self.osm_ids[arc.index], arc.cost,
+                    arc.way.map(|way| way.to_string()).unwrap_or_default(),
+                    escape_csv(self.arc_class(arc).unwrap_or("")), escape_csv(self.arc_name(arc).unwrap_or(""))
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // One `LINESTRING (lon lat, lon lat)` per road segment, for bulk loading
+    // into spatial databases (e.g. PostGIS via `COPY`). Arcs that have an
+    // arc back are only yielded once; segments whose nodes lack coordinates
+    // are left out. WKT puts the longitude first.
+    pub fn to_wkt_lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.adjacent_arcs.iter().enumerate()
+            .flat_map(|(index, arcs)| arcs.iter().map(move |arc| (index, arc.index)))
+            .filter(move |&(index, head)| {
+                index < head || !self.adjacent_arcs[head].iter().any(|arc| arc.index == index)
+            })
+            .filter_map(move |(index, head)| {
+                let from = self.point_by_index(index)?;
+                let to = self.point_by_index(head)?;
+                Some(format!("LINESTRING ({} {}, {} {})", from.lon, from.lat, to.lon, to.lat))
+            })
+    }
+
+    // DIMACS shortest path format (`.gr`): nodes are numbered from 1 in index
+    // order, arc weights are the costs in seconds.
+    pub fn write_dimacs<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "c generated by hello-rust")?;
+        writeln!(writer, "p sp {} {}", self.node_count(), self.arc_count())?;
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                writeln!(writer, "a {} {} {}", index + 1, arc.index + 1, arc.cost)?;
+            }
+        }
+        Ok(())
+    }
+
+    // GraphML with OSM IDs as node IDs, coordinates as node data and costs
+    // as edge data.
+    pub fn write_graphml<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(writer, r#" <key id="lat" for="node" attr.name="lat" attr.type="float"/>"#)?;
+        writeln!(writer, r#" <key id="lon" for="node" attr.name="lon" attr.type="float"/>"#)?;
+        writeln!(writer, r#" <key id="cost" for="edge" attr.name="cost" attr.type="long"/>"#)?;
+        writeln!(writer, r#" <graph id="road_network" edgedefault="directed">"#)?;
+        for osm_id in &self.osm_ids {
+            match self.nodes.get(osm_id) {
+                Some(location) => writeln!(
+                    writer, r#"  <node id="n{}"><data key="lat">{}</data><data key="lon">{}</data></node>"#,
+                    osm_id, location.lat, location.lon
+                )?,
+                None => writeln!(writer, r#"  <node id="n{}"/>"#, osm_id)?,
+            }
+        }
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                writeln!(
+                    writer, r#"  <edge source="n{}" target="n{}"><data key="cost">{}</data></edge>"#,
+                    self.osm_ids[index], self.osm_ids[arc.index], arc.cost
+                )?;
+            }
+        }
+        writeln!(writer, " </graph>")?;
+        writeln!(writer, "</graphml>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::fixtures;
+
+    #[test]
+    fn adjacency_lines_use_osm_ids() {
+        let network = fixtures::network(&[(3, 5, true), (5, 4, false)]);
+        let mut lines = Vec::new();
+        network.write_adjacency(&mut lines).unwrap();
+        let (near, far) = (network.shortest_path(3, 5).unwrap().0, network.shortest_path(5, 4).unwrap().0);
+        assert_eq!(String::from_utf8(lines).unwrap(), format!("3: 5 ({0}s)\n5: 3 ({0}s), 4 ({1}s)\n4:\n", near, far));
+    }
+
+    #[test]
+    fn write_osm_round_trip() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49.001, 7_f32), (3, 49.001, 7.001), (4, 49.002, 7.001)],
+            &[(10, &[1, 2, 3], &[("highway", "residential"), ("name", "Hauptstraße")]),
+              (11, &[3, 4], &[("highway", "motorway"), ("oneway", "yes")])],
+        );
+        let network = fixtures::parse(&xml);
+        let mut written = Vec::new();
+        network.write_osm(&mut written).unwrap();
+        let reparsed = fixtures::parse(std::str::from_utf8(&written).unwrap());
+        assert_eq!((reparsed.node_count(), reparsed.arc_count()), (4, 5));
+        assert_eq!(reparsed.nodes, network.nodes);
+        assert_eq!(fixtures::sorted_arcs(&reparsed), fixtures::sorted_arcs(&network));
+    }
+
+    // Parsed again from `write_osm_xml`.
+    fn reparsed(network: &crate::RoadNetwork) -> (String, crate::RoadNetwork) {
+        let mut written = Vec::new();
+        network.write_osm_xml(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        let reparsed = fixtures::parse(&written);
+        (written, reparsed)
+    }
+
+    #[test]
+    fn write_osm_xml_round_trip() {
+        let xml = fixtures::osm_xml(
+            &[(-1, 49_f32, 7_f32), (2, 49.001, 7_f32), (3, 49.001, 7.001), (4, 49.002, 7.001), (5, 49.003, 7.002), (6, 49.01, 7.01)],
+            &[(10, &[-1, 2, 3], &[("highway", "residential"), ("name", "Straße &amp; &lt;Gasse&gt;")]),
+              (-11, &[3, 4], &[("highway", "motorway"), ("oneway", "yes"), ("maxspeed", "100")]),
+              (12, &[4, 5, 3], &[("highway", "primary"), ("maxspeed", "70")]),
+              (13, &[5, 6], &[("route", "ferry"), ("duration", "00:20")])],
+        );
+        let network = fixtures::parse(&xml);
+        let (written, reparsed) = reparsed(&network);
+        assert!(reparsed.structurally_equal(&network), "{}", written);
+        assert_eq!((reparsed.node_count(), reparsed.arc_count()), (network.node_count(), network.arc_count()));
+        assert!(written.contains(r#"<node id="-1" lat="49" lon="7"/>"#), "{}", written);
+        assert!(written.contains(r#"<tag k="name" v="Straße &amp; &lt;Gasse&gt;"/>"#), "{}", written);
+        assert!(written.contains(r#"<tag k="route" v="ferry"/>"#), "{}", written);
+        // every way is written once with its own ID
+        for way in [10, -11, 12, 13] {
+            assert_eq!(written.matches(&format!(r#"<way id="{}">"#, way)).count(), 1, "{}", written);
+        }
+        let classes = |network: &crate::RoadNetwork| {
+            let mut classes: Vec<(isize, isize, Option<String>)> = network.adjacent_arcs().iter().enumerate()
+                .flat_map(|(index, arcs)| arcs.iter().map(move |arc| (index, arc)))
+                .map(|(index, arc)| (network.osm_ids[index], network.osm_ids[arc.index], network.arc_class(arc).map(String::from)))
+                .collect();
+            classes.sort();
+            classes
+        };
+        assert_eq!(classes(&reparsed), classes(&network));
+    }
+
+    #[test]
+    fn write_osm_xml_splits_what_one_way_cannot_hold() {
+        // arcs added without a way get new, negative IDs below the existing ones
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, false)]);
+        network.add_node(4, crate::Point {lat: 49.001, lon: 7.003});
+        network.add_arc(3, 4, 25_f32);
+        let (written, reparsed) = reparsed(&network);
+        assert!(reparsed.structurally_equal(&network), "{}", written);
+        assert!(written.contains(r#"<tag k="highway" v="road"/>"#), "{}", written);
+        let way_ids: Vec<&str> = written.lines().filter_map(|line| line.trim().strip_prefix(r#"<way id=""#)).collect();
+        assert!(way_ids.len() >= 3 && way_ids.iter().all(|id| id.starts_with('-')), "{}", written);
+        let mut unique = way_ids.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), way_ids.len());
+    }
+
+    #[test]
+    fn non_ascii_names_survive_the_exports() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49.001, 7_f32), (3, 49.001, 7.001)],
+            &[(10, &[1, 2], &[("highway", "residential"), ("name", "Straße &amp; Gasse")]),
+              (11, &[2, 3], &[("highway", "service"), ("name", "&#x6771;&#20140;, &quot;Nord&quot;")])],
+        );
+        let network = fixtures::parse(&xml);
+        let mut csv = Vec::new();
+        network.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "from,to,cost,way_id,class,name");
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1..].iter().filter(|line| line.ends_with(",10,residential,Straße & Gasse")).count() == 2, "{}", csv);
+        assert!(lines[1..].iter().filter(|line| line.ends_with(r#",11,service,"東京, ""Nord""""#)).count() == 2, "{}", csv);
+
+        let mut geojson = Vec::new();
+        network.write_geojson(&mut geojson).unwrap();
+        let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+        let names: HashSet<&str> = geojson["features"].as_array().unwrap().iter()
+            .map(|feature| feature["properties"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, HashSet::from(["Straße & Gasse", "東京, \"Nord\""]));
+    }
+
+    #[test]
+    fn wkt_lines_put_the_longitude_first() {
+        let mut network = fixtures::network(&[(1, 2, true), (3, 2, false)]);
+        network.add_node(4, crate::Point {lat: 49.5, lon: 7.25});
+        network.add_oneway_arc(4, 1, 10_f32);
+        network.add_oneway_arc(1, 4, 10_f32);
+        // without coordinates, only with `MissingCoordPolicy::ZeroCost`
+        network.get_or_create_index(5);
+        let lines: Vec<String> = network.to_wkt_lines().collect();
+        assert_eq!(lines, [
+            "LINESTRING (7.001 49, 7.002 49)",
+            "LINESTRING (7.001 49, 7.25 49.5)",
+            "LINESTRING (7.003 49, 7.002 49)",
+        ]);
+    }
+
+    #[test]
+    fn arcs_from_way_1234_carry_its_id() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49.001, 7_f32), (3, 49.001, 7.001)],
+            &[(1234, &[1, 2, 3], &[("highway", "residential")]), (77, &[3, 1], &[("highway", "service"), ("oneway", "yes")])],
+        );
+        let network = fixtures::parse(&xml);
+        let ways: Vec<Option<isize>> = network.adjacent_arcs().iter().flatten().map(|arc| arc.way).collect();
+        assert_eq!(ways.iter().filter(|&&way| way == Some(1234)).count(), 4);
+        assert_eq!(ways.iter().filter(|&&way| way == Some(77)).count(), 1);
+        assert_eq!(network.way_ids_along(&[1, 2, 3, 1]), vec![1234, 77]);
+
+        let mut csv = Vec::new();
+        network.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let row = |from: isize, to: isize| format!("{},{},{},", from, to, fixtures::path_cost(&network, &[from, to]).unwrap());
+        assert!(csv.lines().any(|line| line == format!("{}1234,residential,", row(1, 2))), "{}", csv);
+        assert!(csv.lines().any(|line| line == format!("{}77,service,", row(3, 1))), "{}", csv);
+        // arcs added without a way have an empty column
+        let mut csv = Vec::new();
+        fixtures::network(&[(1, 2, false)]).write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().nth(1), Some("1,2,7,,,"));
+
+        let mut geojson = Vec::new();
+        network.write_geojson(&mut geojson).unwrap();
+        let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+        let ways: HashSet<i64> = geojson["features"].as_array().unwrap().iter()
+            .map(|feature| feature["properties"]["way"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ways, HashSet::from([1234, 77]));
+    }
+}
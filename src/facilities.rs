@@ -0,0 +1,114 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::RoadNetwork;
+
+impl RoadNetwork {
+    // The source (e.g. one of several fire stations) that reaches `target`
+    // fastest, with its travel time. One Dijkstra run started from all
+    // sources at once; equally fast sources go to the lowest OSM ID. Sources
+    // that aren't part of the graph are ignored.
+    pub fn nearest_of(&self, sources: &[isize], target: isize) -> Option<(isize, usize)> {
+        let target = self.get_index(target)?;
+        let nearest = self._multi_source_dijkstra(sources, Some(target), None);
+        nearest[target]
+    }
+
+    // The fastest source and its travel time for every node reached within
+    // `max_cost` seconds (if given), by OSM ID, e.g. for catchment areas.
+    // Ties are broken as in `nearest_of`.
+    pub fn nearest_source_map(&self, sources: &[isize], max_cost: Option<usize>) -> HashMap<isize, (isize, usize)> {
+        self._multi_source_dijkstra(sources, None, max_cost).into_iter().enumerate()
+            .filter_map(|(index, nearest)| Some((self.osm_ids[index], nearest?)))
+            .collect()
+    }
+
+    // `(source, cost)` by index for the settled nodes. Queue entries and
+    // labels compare by cost, then by source OSM ID, so a node settles with
+    // the lowest of its fastest sources.
+    fn _multi_source_dijkstra(
+        &self, sources: &[isize], target: Option<usize>, max_cost: Option<usize>
+    ) -> Vec<Option<(isize, usize)>> {
+        let mut labels: Vec<Option<(usize, isize)>> = vec![None; self.adjacent_arcs.len()];
+        let mut settled = vec![false; self.adjacent_arcs.len()];
+        let mut queue: BinaryHeap<Reverse<(usize, isize, usize)>> = BinaryHeap::new();
+        for &source in sources {
+            let Some(index) = self.get_index(source) else { continue };
+            if labels[index].is_none_or(|label| (0, source) < label) {
+                labels[index] = Some((0, source));
+                queue.push(Reverse((0, source, index)));
+            }
+        }
+
+        while let Some(Reverse((cost, source, index))) = queue.pop() {
+            if settled[index] {
+                continue;
+            }
+            if max_cost.is_some_and(|max_cost| cost > max_cost) {
+                break;
+            }
+            settled[index] = true;
+            if Some(index) == target {
+                break;
+            }
+            for arc in &self.adjacent_arcs[index] {
+                let label = (cost.saturating_add(arc.cost), source);
+                if labels[arc.index].is_none_or(|current| label < current) {
+                    labels[arc.index] = Some(label);
+                    queue.push(Reverse((label.0, source, arc.index)));
+                }
+            }
+        }
+        labels.into_iter().zip(settled)
+            .map(|(label, settled)| label.filter(|_| settled).map(|(cost, source)| (source, cost)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, Point, RoadNetwork};
+
+    // Facilities 1 and 5 two hops from node 3 either way, over a fast road
+    // from 1 and a slow one from 5.
+    fn two_facilities() -> RoadNetwork {
+        let mut network: RoadNetwork = (1..=5).map(|osm_id| (osm_id, Point {lat: 49_f32, lon: 7_f32 + osm_id as f32 * 0.001})).collect();
+        network.add_arcs([(1, 2, 20_f32), (2, 3, 20_f32), (3, 4, 5_f32), (4, 5, 5_f32)]);
+        network
+    }
+
+    #[test]
+    fn nearest_by_cost_not_by_hops() {
+        let network = two_facilities();
+        let cost = fixtures::path_cost(&network, &[1, 2, 3]).unwrap();
+        assert!(cost < fixtures::path_cost(&network, &[5, 4, 3]).unwrap());
+        assert_eq!(network.nearest_of(&[5, 1], 3), Some((1, cost)));
+        assert_eq!(network.nearest_of(&[5, 1], 4), Some((5, fixtures::path_cost(&network, &[5, 4]).unwrap())));
+        assert_eq!(network.nearest_of(&[5, 1], 5), Some((5, 0)));
+        // unknown sources are ignored, unknown targets have no nearest source
+        assert_eq!(network.nearest_of(&[99, 5], 3), Some((5, fixtures::path_cost(&network, &[5, 4, 3]).unwrap())));
+        assert_eq!(network.nearest_of(&[1, 5], 99), None);
+        assert_eq!(network.nearest_of(&[], 3), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_lowest_source() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        assert_eq!(network.nearest_of(&[3, 1], 2).unwrap().0, 1);
+        assert_eq!(network.nearest_source_map(&[3, 1], None)[&2].0, 1);
+    }
+
+    #[test]
+    fn catchment_areas() {
+        let network = two_facilities();
+        let map = network.nearest_source_map(&[1, 5], None);
+        assert_eq!(map.len(), 5);
+        for osm_id in 1..=5 {
+            assert_eq!(Some(map[&osm_id]), network.nearest_of(&[1, 5], osm_id));
+        }
+        let (_, cost) = map[&4];
+        let within = network.nearest_source_map(&[1, 5], Some(cost));
+        assert!(within.contains_key(&4) && within.contains_key(&5));
+        assert!(within.values().all(|&(_, reached_cost)| reached_cost <= cost));
+    }
+}
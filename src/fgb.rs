@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use flatgeobuf::{ColumnType, FgbWriter, GeometryType};
+use geozero::error::Result as GeozeroResult;
+use geozero::{ColumnValue, GeomProcessor, GeozeroGeometry, PropertyProcessor};
+
+use crate::{Point, RoadNetwork};
+
+// Straight line between two nodes, as FlatGeobuf wants its geometries.
+struct Segment(Point, Point);
+
+impl GeozeroGeometry for Segment {
+    fn process_geom<P: GeomProcessor>(&self, processor: &mut P) -> GeozeroResult<()> {
+        processor.linestring_begin(true, 2, 0)?;
+        processor.xy(self.0.lon as f64, self.0.lat as f64, 0)?;
+        processor.xy(self.1.lon as f64, self.1.lat as f64, 1)?;
+        processor.linestring_end(true, 0)
+    }
+}
+
+impl RoadNetwork {
+    // One LineString feature per undirected edge with the properties `from`,
+    // `to`, `cost` (of the `from -> to` arc), `class`, `name` and `way`. The file
+    // is Hilbert-sorted with a spatial index, so the features are not in
+    // graph order.
+    pub fn write_flatgeobuf<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut fgb = FgbWriter::create("roads", GeometryType::LineString).map_err(io::Error::other)?;
+        fgb.add_column("from", ColumnType::Long, |_, _| {});
+        fgb.add_column("to", ColumnType::Long, |_, _| {});
+        fgb.add_column("cost", ColumnType::ULong, |_, _| {});
+        fgb.add_column("class", ColumnType::String, |_, column| column.nullable = true);
+        fgb.add_column("name", ColumnType::String, |_, column| column.nullable = true);
+        fgb.add_column("way", ColumnType::Long, |_, column| column.nullable = true);
+
+        let mut seen = HashSet::new();
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            let osm_id = self.osm_ids[index];
+            let Some(&from) = self.nodes.get(&osm_id) else { continue };
+            for arc in arcs {
+                let neighbor = self.osm_ids[arc.index];
+                let Some(&to) = self.nodes.get(&neighbor) else { continue };
+                if !seen.insert((osm_id.min(neighbor), osm_id.max(neighbor))) {
+                    continue;
+                }
+                let (class, name) = (self.arc_class(arc), self.arc_name(arc));
+                fgb.add_feature_geom(Segment(from, to), |feature| {
+                    // only fails for unknown column indices
+                    let _ = feature.property(0, "from", &ColumnValue::Long(osm_id as i64));
+                    let _ = feature.property(1, "to", &ColumnValue::Long(neighbor as i64));
+                    let _ = feature.property(2, "cost", &ColumnValue::ULong(arc.cost as u64));
+                    if let Some(class) = class {
+                        let _ = feature.property(3, "class", &ColumnValue::String(class));
+                    }
+                    if let Some(name) = name {
+                        let _ = feature.property(4, "name", &ColumnValue::String(name));
+                    }
+                    if let Some(way) = arc.way {
+                        let _ = feature.property(5, "way", &ColumnValue::Long(way as i64));
+                    }
+                }).map_err(io::Error::other)?;
+            }
+        }
+        fgb.write(writer).map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use flatgeobuf::{FallibleStreamingIterator, FeatureProperties, FgbReader};
+
+    use crate::RoadNetwork;
+
+    #[test]
+    fn features_read_back() {
+        let mut network = RoadNetwork::new();
+        network.read_from_osm_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm")).unwrap();
+        let mut buffer = Vec::new();
+        network.write_flatgeobuf(&mut buffer).unwrap();
+
+        let mut features = FgbReader::open(Cursor::new(buffer)).unwrap().select_all().unwrap();
+        // 9 arcs: four roads in both directions and the one-way 5 -> 7
+        assert_eq!(features.features_count(), Some(5));
+        let mut edges = Vec::new();
+        while let Some(feature) = features.next().unwrap() {
+            let (from, to) = (feature.property::<i64>("from").unwrap(), feature.property::<i64>("to").unwrap());
+            let way = feature.property::<i64>("way").unwrap();
+            edges.push((from.min(to), from.max(to), way, feature.property::<String>("class").unwrap()));
+        }
+        edges.sort();
+        assert_eq!(edges, vec![
+            (1, 2, 100, "residential".to_string()),
+            (2, 3, 100, "residential".to_string()),
+            (2, 4, 101, "primary".to_string()),
+            (4, 5, 101, "primary".to_string()),
+            (5, 7, 103, "residential".to_string()),
+        ]);
+    }
+}
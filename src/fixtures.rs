@@ -0,0 +1,108 @@
+// Small networks shared by the unit tests.
+use crate::{Point, RoadNetwork};
+
+pub type Way<'a> = (isize, &'a [isize], &'a [(&'a str, &'a str)]);
+
+// OSM XML with nodes `(id, lat, lon)` and ways `(id, refs, tags)`, one child
+// per line as osmium writes it. Tag values are written as given, so escape
+// them in the test if needed.
+pub fn osm_xml(nodes: &[(isize, f32, f32)], ways: &[Way]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"0.6\" generator=\"fixture\">\n");
+    for (osm_id, lat, lon) in nodes {
+        xml.push_str(&format!(" <node id=\"{}\" lat=\"{}\" lon=\"{}\"/>\n", osm_id, lat, lon));
+    }
+    for (way_id, refs, tags) in ways {
+        xml.push_str(&format!(" <way id=\"{}\">\n", way_id));
+        for node_ref in refs.iter() {
+            xml.push_str(&format!("  <nd ref=\"{}\"/>\n", node_ref));
+        }
+        for (key, value) in tags.iter() {
+            xml.push_str(&format!("  <tag k=\"{}\" v=\"{}\"/>\n", key, value));
+        }
+        xml.push_str(" </way>\n");
+    }
+    xml.push_str("</osm>\n");
+    xml
+}
+
+// `xml` read into a network with the default (car) profile.
+pub fn parse(xml: &str) -> RoadNetwork {
+    parse_into(RoadNetwork::new(), xml)
+}
+
+// `xml` read into a configured network.
+pub fn parse_into(mut network: RoadNetwork, xml: &str) -> RoadNetwork {
+    network.read_from_reader(xml.as_bytes()).unwrap();
+    network
+}
+
+// `size` x `size` nodes 0.001° apart, starting at 49°N 7°E, with roads in
+// both directions between neighbors at 10 m/s. Node IDs are
+// `row * size + column + 1`, rows going north and columns east.
+pub fn grid(size: usize) -> RoadNetwork {
+    let osm_id = |row: usize, column: usize| (row * size + column + 1) as isize;
+    let mut network = RoadNetwork::new();
+    for row in 0..size {
+        for column in 0..size {
+            network.add_node(osm_id(row, column), Point {lat: 49_f32 + row as f32 * 0.001, lon: 7_f32 + column as f32 * 0.001});
+        }
+    }
+    for row in 0..size {
+        for column in 0..size {
+            if column + 1 < size {
+                network.add_arc(osm_id(row, column), osm_id(row, column + 1), 10_f32);
+            }
+            if row + 1 < size {
+                network.add_arc(osm_id(row, column), osm_id(row + 1, column), 10_f32);
+            }
+        }
+    }
+    network
+}
+
+// Network from `(tail, head, bidirectional)` arcs at 10 m/s. Node `n` lies
+// at 49°N, 7° + n * 0.001°E, so nodes with consecutive IDs are about 73 m
+// apart.
+pub fn network(arcs: &[(isize, isize, bool)]) -> RoadNetwork {
+    let mut network = RoadNetwork::new();
+    for &(tail, head, _) in arcs {
+        for osm_id in [tail, head] {
+            network.add_node(osm_id, Point {lat: 49_f32, lon: 7_f32 + osm_id as f32 * 0.001});
+        }
+    }
+    for &(tail, head, bidirectional) in arcs {
+        if bidirectional {
+            network.add_arc(tail, head, 10_f32);
+        } else {
+            network.add_oneway_arc(tail, head, 10_f32);
+        }
+    }
+    network
+}
+
+// Whether there is an arc from `from` to `to`.
+pub fn has_arc(network: &RoadNetwork, from: isize, to: isize) -> bool {
+    let (Some(from), Some(to)) = (network.get_index(from), network.get_index(to)) else { return false };
+    network.adjacent_arcs()[from].iter().any(|arc| arc.index == to)
+}
+
+// All arcs as `(tail, head, cost, name)` by OSM ID, sorted, to compare
+// networks regardless of the order nodes got their indices in.
+pub fn sorted_arcs(network: &RoadNetwork) -> Vec<(isize, isize, usize, Option<String>)> {
+    let mut arcs: Vec<_> = network.adjacent_arcs().iter().enumerate()
+        .flat_map(|(index, arcs)| arcs.iter().map(move |arc| (index, arc)))
+        .map(|(index, arc)| (
+            network.osm_ids[index], network.osm_ids[arc.index], arc.cost, network.arc_name(arc).map(String::from)
+        ))
+        .collect();
+    arcs.sort_unstable();
+    arcs
+}
+
+// Sum of the cheapest arcs along `path`, `None` if a step has no arc.
+pub fn path_cost(network: &RoadNetwork, path: &[isize]) -> Option<usize> {
+    path.windows(2).map(|pair| {
+        let (tail, head) = (network.get_index(pair[0])?, network.get_index(pair[1])?);
+        network.adjacent_arcs()[tail].iter().filter(|arc| arc.index == head).map(|arc| arc.cost).min()
+    }).sum()
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// Stores each distinct string once, so arcs can refer to them by index.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub(crate) struct Interner {
+    strings: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, string: &str) -> usize {
+        if let Some(&id) = self.ids.get(string) {
+            return id;
+        }
+        let id = self.strings.len();
+        self.strings.push(string.to_string());
+        self.ids.insert(string.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&str> {
+        self.strings.get(id).map(String::as_str)
+    }
+}
+
+impl From<Vec<String>> for Interner {
+    fn from(strings: Vec<String>) -> Interner {
+        let ids = strings.iter().enumerate().map(|(id, string)| (string.clone(), id)).collect();
+        Interner {strings, ids}
+    }
+}
+
+impl From<Interner> for Vec<String> {
+    fn from(interner: Interner) -> Vec<String> {
+        interner.strings
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{Point, RoadNetwork};
+
+// How far (in meters) the isochrone polygon extends beyond the outermost
+// reachable nodes, so none of them lies exactly on its boundary.
+const ISOCHRONE_BUFFER: f32 = 50_f32;
+
+impl RoadNetwork {
+    // Nodes reachable from `source` within `max_cost` seconds (including
+    // `source` itself), sorted by OSM ID. Empty if `source` is unknown.
+    pub fn reachable_within(&self, source: isize, max_cost: usize) -> Vec<isize> {
+        let Some(index) = self.get_index(source) else { return Vec::new() };
+        let Ok(search) = self._dijkstra(index, None, None, Some(max_cost), |_, _, arc| Some(arc.cost)) else {
+            return Vec::new();
+        };
+        let mut reachable: Vec<isize> = search.costs.iter().enumerate()
+            .filter(|&(_, &cost)| cost <= max_cost)
+            .map(|(index, _)| self.osm_ids[index])
+            .collect();
+        reachable.sort_unstable();
+        reachable
+    }
+
+    // Area reachable from `source` within `max_cost` seconds as a closed,
+    // counterclockwise ring (first point repeated at the end, as GeoJSON
+    // wants it). This is the convex hull of the reachable nodes, widened by
+    // `ISOCHRONE_BUFFER`: it never cuts off a reachable node, but it also
+    // covers unreachable gaps (valleys, water) between reachable areas.
+    pub fn travel_time_isochrone_polygon(&self, source: isize, max_cost: usize) -> Vec<Point> {
+        let points: Vec<Point> = self.reachable_within(source, max_cost).iter()
+            .filter_map(|osm_id| self.nodes.get(osm_id).copied())
+            .collect();
+        // buffering the hull's corners is enough, the rest lies inside anyway
+        let buffered: Vec<Point> = convex_hull(points).into_iter().flat_map(buffer).collect();
+        let mut ring = convex_hull(buffered);
+        if let Some(&first) = ring.first() {
+            ring.push(first);
+        }
+        ring
+    }
+}
+
+// Eight points around `point` at `ISOCHRONE_BUFFER` meters.
+fn buffer(point: Point) -> impl Iterator<Item = Point> {
+    let lat_radius = ISOCHRONE_BUFFER / 111_229_f32;
+    let lon_radius = lat_radius / point.lat.to_radians().cos().max(0.01_f32);
+    (0..8).map(move |step| {
+        let angle = step as f32 * std::f32::consts::FRAC_PI_4;
+        Point {lat: point.lat + lat_radius * angle.sin(), lon: point.lon + lon_radius * angle.cos()}
+    })
+}
+
+// Andrew's monotone chain on (lon, lat), counterclockwise, without repeating
+// the first point.
+fn convex_hull(mut points: Vec<Point>) -> Vec<Point> {
+    points.sort_by(|a, b| a.lon.total_cmp(&b.lon).then(a.lat.total_cmp(&b.lat)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull = half_hull(points.iter());
+    hull.extend(half_hull(points.iter().rev()));
+    hull
+}
+
+// Lower (or, walking backwards, upper) part of the hull, without its last
+// point, which starts the other part.
+fn half_hull<'a>(points: impl Iterator<Item = &'a Point>) -> Vec<Point> {
+    let cross = |o: Point, a: Point, b: Point| {
+        (a.lon - o.lon) * (b.lat - o.lat) - (a.lat - o.lat) * (b.lon - o.lon)
+    };
+    let mut half: Vec<Point> = Vec::new();
+    for &point in points {
+        while half.len() >= 2 && cross(half[half.len() - 2], half[half.len() - 1], point) <= 0_f32 {
+            half.pop();
+        }
+        half.push(point);
+    }
+    half.pop();
+    half
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, Point};
+
+    // Ray casting on (lon, lat).
+    fn contains(ring: &[Point], point: Point) -> bool {
+        let mut inside = false;
+        for edge in ring.windows(2) {
+            let (a, b) = (edge[0], edge[1]);
+            if (a.lat > point.lat) != (b.lat > point.lat)
+                && point.lon < a.lon + (point.lat - a.lat) / (b.lat - a.lat) * (b.lon - a.lon) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    #[test]
+    fn polygon_encloses_reachable_nodes() {
+        let network = fixtures::grid(10);
+        let reachable = network.reachable_within(1, 30);
+        assert!(reachable.len() > 3 && !reachable.contains(&100));
+        let ring = network.travel_time_isochrone_polygon(1, 30);
+        assert_eq!(ring.first(), ring.last());
+        for osm_id in reachable {
+            assert!(contains(&ring, network.nodes[&osm_id]), "{} outside", osm_id);
+        }
+        assert!(!contains(&ring, network.nodes[&100]));
+        assert!(network.travel_time_isochrone_polygon(1000, 30).is_empty());
+    }
+
+    #[test]
+    fn polygon_of_a_single_node_is_its_buffer() {
+        let network = fixtures::grid(2);
+        let ring = network.travel_time_isochrone_polygon(1, 0);
+        // eight buffer points, closed
+        assert_eq!(ring.len(), 9);
+        assert!(contains(&ring, network.nodes[&1]));
+        assert!(!contains(&ring, network.nodes[&2]));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+use serde::Deserialize;
+
+use crate::{Point, RoadNetwork, WayTags};
+
+#[derive(Deserialize)]
+struct OverpassResponse {
+    elements: Vec<Element>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Element {
+    Node {
+        id: isize,
+        lat: Option<f32>,
+        lon: Option<f32>,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    },
+    Way {
+        id: isize,
+        #[serde(default)]
+        nodes: Vec<isize>,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl RoadNetwork {
+    // Reads Overpass API JSON output (`[out:json]`). Nodes and ways may come in
+    // any order, ways are only turned into arcs once all nodes are known.
+    pub fn read_from_osm_json<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let response: OverpassResponse = serde_json::from_reader(reader)?;
+        let mut ways = Vec::new();
+        for element in response.elements {
+            match element {
+                Element::Node {id, lat: Some(lat), lon: Some(lon), tags} => {
+                    self.add_node(id, Point {lat, lon});
+                    self._add_node_tags(id, &tags);
+                }
+                Element::Way {id, nodes, tags} => ways.push((id, nodes, tags)),
+                _ => {}
+            }
+        }
+        for (id, hops, tags) in ways {
+            self._add_way(&hops, &WayTags::from_tags(&tags), Some(id))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, RoadNetwork};
+
+    // Ways may come before their nodes in Overpass output.
+    const OVERPASS: &str = r#"{
+        "version": 0.6,
+        "elements": [
+            {"type": "way", "id": 10, "nodes": [1, 2, 3], "tags": {"highway": "residential", "name": "Hauptstraße"}},
+            {"type": "way", "id": 11, "nodes": [3, 4], "tags": {"highway": "primary", "oneway": "yes"}},
+            {"type": "node", "id": 1, "lat": 49.0, "lon": 7.0},
+            {"type": "node", "id": 2, "lat": 49.001, "lon": 7.0},
+            {"type": "node", "id": 3, "lat": 49.001, "lon": 7.001},
+            {"type": "node", "id": 4, "lat": 49.002, "lon": 7.001},
+            {"type": "relation", "id": 20, "members": []}
+        ]
+    }"#;
+
+    #[test]
+    fn overpass_json_matches_xml() {
+        let mut from_json = RoadNetwork::new();
+        from_json.read_from_osm_json(OVERPASS.as_bytes()).unwrap();
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49.001, 7_f32), (3, 49.001, 7.001), (4, 49.002, 7.001)],
+            &[(10, &[1, 2, 3], &[("highway", "residential"), ("name", "Hauptstraße")]),
+              (11, &[3, 4], &[("highway", "primary"), ("oneway", "yes")])],
+        );
+        let from_xml = fixtures::parse(&xml);
+        assert_eq!(from_json.arc_count(), 5);
+        assert_eq!(from_json.nodes, from_xml.nodes);
+        assert_eq!(fixtures::sorted_arcs(&from_json), fixtures::sorted_arcs(&from_xml));
+        assert_eq!(from_json.way_ids_along(&[1, 2, 3, 4]), from_xml.way_ids_along(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        assert!(RoadNetwork::new().read_from_osm_json(&b"{\"elements\": 3}"[..]).is_err());
+    }
+}
@@ -0,0 +1,2013 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Sub;
+use std::str::FromStr;
+use std::io::prelude::*;
+use std::sync;
+
+#[macro_use] extern crate lazy_static;
+extern crate regex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+mod astar;
+#[cfg(feature = "tokio")]
+mod async_read;
+mod cache;
+mod ch;
+mod checkpoint;
+mod components;
+mod conditional;
+mod distance;
+mod export;
+mod facilities;
+#[cfg(feature = "flatgeobuf")]
+mod fgb;
+#[cfg(test)]
+mod fixtures;
+mod interner;
+mod isochrone;
+mod json;
+mod matrix;
+#[cfg(feature = "petgraph")]
+mod petgraph_adapter;
+mod profile;
+mod repl;
+mod round_trip;
+mod routing;
+mod simplify;
+mod spatial;
+mod stats;
+mod vehicle;
+use interner::Interner;
+pub use ch::ContractionHierarchy;
+pub use distance::{DistanceModel, Equirectangular, Euclidean, Haversine};
+#[cfg(feature = "rayon")]
+pub use matrix::set_thread_count;
+pub use profile::{LanesSpeedFactor, ProfileError, SpeedProfile};
+pub use repl::REPL_USAGE;
+pub use routing::{CostBreakdown, Route, RoutingError, ShortestPathTree, Snap, SnapPolicy, DEFAULT_SNAP_RADIUS};
+pub use simplify::StageReport;
+pub use stats::NetworkReport;
+pub use vehicle::Dimensions;
+
+lazy_static! {
+    static ref OSM_NODE_RE: Regex =
+        Regex::new(r#"id="(-?\d+)" lat="(-?[0-9.]+)" lon="(-?[0-9.]+)""#).unwrap();
+    static ref OSM_WAY_ID_RE: Regex = Regex::new(r#"\sid="(-?\d+)""#).unwrap();
+    static ref OSM_TAG_RE: Regex = Regex::new(r#"<tag k="([^"]*)" v="([^"]*)""#).unwrap();
+    static ref OSM_ND_RE: Regex = Regex::new(r#"<nd ref="(-?\d+)""#).unwrap();
+    static ref OSM_VERSION_RE: Regex = Regex::new(r#"\sversion="([^"]*)""#).unwrap();
+    static ref OSM_GENERATOR_RE: Regex = Regex::new(r#"\sgenerator="([^"]*)""#).unwrap();
+}
+
+pub(crate) const KMPH: f32 = 1000_f32 / 3600_f32;  // km/h to m/s factor
+// Upper bound for a single arc's cost (about 34 years), far below `usize::MAX`
+// (which marks unreached nodes) even on 32-bit targets.
+pub const MAX_ARC_COST: usize = 1 << 30;
+const PROGRESS_INTERVAL: usize = 100_000;  // lines between two progress reports
+
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Arc {
+    pub index: usize,
+    pub cost: usize,  // in seconds
+    pub name: Option<usize>,  // see `RoadNetwork::arc_name`
+    #[serde(default)]
+    pub class: Option<usize>,  // see `RoadNetwork::arc_class`
+    #[serde(default)]
+    pub way: Option<isize>,  // OSM ID of the way the arc was created from, if known
+    #[serde(default)]
+    pub length: Option<f32>,  // in meters, `None` if a node had no coordinates
+}
+
+// What the arcs built from one way share.
+#[derive(Debug, Copy, Clone, Default)]
+struct ArcOrigin {
+    name: Option<usize>,
+    class: Option<usize>,
+    way: Option<isize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub lat: f32,
+    pub lon: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct CostSummary {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f32,
+    pub zero_cost: usize,  // usually a sign of truncated costs
+}
+
+// The tags of a way that matter for building arcs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WayTags {
+    pub highway: Option<String>,
+    pub maxspeed: Option<String>,
+    pub maxspeed_conditional: Option<String>,
+    pub oneway: Option<String>,
+    pub junction: Option<String>,
+    pub name: Option<String>,
+    pub access: Option<String>,
+    pub tracktype: Option<String>,
+    pub lanes: Option<String>,
+    pub maxweight: Option<String>,
+    pub maxheight: Option<String>,
+    pub maxwidth: Option<String>,
+    pub route: Option<String>,  // "ferry" makes a way routable without a highway tag
+    pub duration: Option<String>,  // crossing time of a ferry, "HH:MM" or minutes
+}
+
+impl WayTags {
+    // The relevant ones of a way's tags, e.g. as collected by `parse_tag_line`.
+    pub fn from_tags(tags: &HashMap<String, String>) -> WayTags {
+        let mut way_tags = WayTags::default();
+        for (key, value) in tags {
+            way_tags.set(key, value);
+        }
+        way_tags
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        let field = match key {
+            "highway" => &mut self.highway,
+            "maxspeed" => &mut self.maxspeed,
+            "maxspeed:conditional" => &mut self.maxspeed_conditional,
+            "oneway" => &mut self.oneway,
+            "junction" => &mut self.junction,
+            "name" => &mut self.name,
+            "access" => &mut self.access,
+            "tracktype" => &mut self.tracktype,
+            "lanes" => &mut self.lanes,
+            "maxweight" => &mut self.maxweight,
+            "maxheight" => &mut self.maxheight,
+            "maxwidth" => &mut self.maxwidth,
+            "route" => &mut self.route,
+            "duration" => &mut self.duration,
+            _ => return,
+        };
+        *field = Some(value.to_string());
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        IntoIterator::into_iter([
+            ("highway", &self.highway),
+            ("maxspeed", &self.maxspeed),
+            ("maxspeed:conditional", &self.maxspeed_conditional),
+            ("oneway", &self.oneway),
+            ("junction", &self.junction),
+            ("name", &self.name),
+            ("access", &self.access),
+            ("tracktype", &self.tracktype),
+            ("lanes", &self.lanes),
+            ("maxweight", &self.maxweight),
+            ("maxheight", &self.maxheight),
+            ("maxwidth", &self.maxwidth),
+            ("route", &self.route),
+            ("duration", &self.duration),
+        ]).filter_map(|(key, value)| value.as_deref().map(|value| (key, value)))
+    }
+}
+
+// How far `read_from_reader_with_progress` got.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ParseProgress {
+    pub bytes_read: u64,
+    pub total_bytes: Option<u64>,  // if known, e.g. the file size
+    pub nodes: usize,
+    pub ways: usize,  // all closed ways, routable or not
+}
+
+// Data problems worked around while building arcs, summed over all reads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ParseStats {
+    pub duplicate_refs: usize,  // a node listed twice in a row, would give a self-loop
+    pub rejected_long_arcs: usize,  // longer than `with_max_arc_length` allows
+    pub unparsed_conditions: usize,  // `maxspeed:conditional` values that were ignored
+    #[serde(default)]
+    pub invalid_utf8_lines: usize,  // parsed with the bad bytes replaced by U+FFFD
+    #[serde(default)]
+    pub self_loops: usize,  // arcs from a node to itself, never added
+    #[serde(default)]
+    pub zero_length_arcs: usize,  // between distinct nodes at the same location, cost raised to 1
+    #[serde(default)]
+    pub filtered_ways: usize,  // roads left out by the `HighwayFilter`
+}
+
+// Provenance from the `<osm version=".." generator="..">` header.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SourceMetadata {
+    pub version: Option<String>,  // of the OSM API / file format, usually "0.6"
+    pub generator: Option<String>,  // the tool that wrote the file
+}
+
+// What to do with way segments whose nodes have no coordinates (usually
+// because they lie outside of the extract).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MissingCoordPolicy {
+    #[default]
+    Skip,  // drop the segment
+    // Keep the segment with cost 0. Preserves connectivity, but routes may
+    // take such segments as free shortcuts, and the node can't be snapped to.
+    ZeroCost,
+    Error,  // abort reading with `OsmError::MissingCoordinates`
+}
+
+// Which highway classes are turned into arcs at all, checked before the
+// speed profile. Unlike blocking classes in the profile, this is meant to
+// keep large extracts small, e.g. motorways only for long-distance routing.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HighwayFilter {
+    #[default]
+    All,
+    Only(Vec<String>),
+    Exclude(Vec<String>),
+}
+
+impl HighwayFilter {
+    pub fn only(classes: &[&str]) -> HighwayFilter {
+        HighwayFilter::Only(classes.iter().map(|class| class.to_string()).collect())
+    }
+
+    pub fn exclude(classes: &[&str]) -> HighwayFilter {
+        HighwayFilter::Exclude(classes.iter().map(|class| class.to_string()).collect())
+    }
+
+    // Ferries count as class "ferry".
+    pub fn allows(&self, class: &str) -> bool {
+        match self {
+            HighwayFilter::All => true,
+            HighwayFilter::Only(classes) => classes.iter().any(|allowed| allowed == class),
+            HighwayFilter::Exclude(classes) => !classes.iter().any(|excluded| excluded == class),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OsmError {
+    MissingCoordinates(isize),  // the node without coordinates
+    MissingNode(isize),  // an OSM ID without a known location
+}
+
+impl fmt::Display for OsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OsmError::MissingCoordinates(osm_id) => write!(f, "node {} has no coordinates", osm_id),
+            OsmError::MissingNode(osm_id) => write!(f, "node {} is unknown", osm_id),
+        }
+    }
+}
+
+impl Error for OsmError {}
+
+// So readers can keep returning `io::Result`, the `OsmError` is available
+// through `io::Error::get_ref`.
+impl From<OsmError> for std::io::Error {
+    fn from(error: OsmError) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoadNetwork {
+    osm_id_map: HashMap<isize, usize>,
+    osm_ids: Vec<isize>,
+    nodes: HashMap<isize, Point>,  // all parsed nodes, in the graph or not
+    // Coordinates of the graph nodes by index, `None` until the node is known.
+    #[serde(default)]
+    points: Vec<Option<Point>>,
+    adjacent_arcs: Vec<Vec<Arc>>,
+    #[serde(default)]
+    barriers: HashMap<isize, String>,  // `barrier=*` value by node
+    names: Interner,  // way names and highway classes of arcs
+    profile: SpeedProfile,
+    #[serde(default)]
+    missing_coord_policy: MissingCoordPolicy,
+    #[serde(default)]
+    max_arc_length: Option<f32>,
+    #[serde(default)]
+    highway_filter: HighwayFilter,
+    #[serde(default)]
+    parse_stats: ParseStats,
+    #[serde(default)]
+    source_metadata: Option<SourceMetadata>,  // of the first file read
+    // by OSM way ID (see `Arc::way`), see `shortest_path_departing_at`
+    #[serde(default)]
+    conditional_speeds: HashMap<isize, Vec<conditional::ConditionalSpeed>>,
+    // by OSM way ID (see `Arc::way`), see `route_respecting_max_weight`
+    #[serde(default)]
+    dimension_limits: HashMap<isize, Dimensions>,
+    #[serde(skip, default = "default_distance_model")]
+    distance_model: sync::Arc<dyn DistanceModel>,
+    #[serde(skip)]
+    speed_bound: sync::OnceLock<astar::SpeedBound>,  // cleared whenever arcs are added
+    #[serde(skip)]
+    component_labels: Option<Vec<usize>>,  // see `compute_components`, cleared like `speed_bound`
+    #[serde(skip)]
+    spatial_index: sync::OnceLock<spatial::SpatialIndex>,  // cleared whenever nodes or arcs are added
+    #[serde(skip)]
+    arcs_per_node: usize,  // initial capacity of new adjacency lists, see `with_capacity`
+}
+
+fn default_distance_model() -> sync::Arc<dyn DistanceModel> {
+    sync::Arc::new(Haversine::default())
+}
+
+// Whether `point` lies in the box spanned by the south-west corner `min` and
+// the north-east corner `max`; `min.lon > max.lon` wraps around the antimeridian.
+fn bbox_contains(min: Point, max: Point, point: Point) -> bool {
+    let contains_lon = if min.lon <= max.lon {
+        min.lon <= point.lon && point.lon <= max.lon
+    } else {
+        min.lon <= point.lon || point.lon <= max.lon
+    };
+    min.lat <= point.lat && point.lat <= max.lat && contains_lon
+}
+
+// `line` without XML comments; `in_comment` carries an unterminated
+// `<!--` over to the next line.
+fn strip_xml_comments<'a>(line: &'a str, in_comment: &mut bool) -> Cow<'a, str> {
+    if !*in_comment && !line.contains("<!--") {
+        return Cow::Borrowed(line);
+    }
+    let mut kept = String::new();
+    let mut rest = line;
+    loop {
+        let delimiter = if *in_comment { "-->" } else { "<!--" };
+        let Some(position) = rest.find(delimiter) else {
+            if !*in_comment {
+                kept.push_str(rest);
+            }
+            return Cow::Owned(kept);
+        };
+        if !*in_comment {
+            kept.push_str(&rest[..position]);
+        }
+        rest = &rest[position + delimiter.len()..];
+        *in_comment = !*in_comment;
+    }
+}
+
+// Parses a `<node id=".." lat=".." lon="..">` line; `None` for any other line
+// or if a number does not fit (overflowing ids, "1.2.3").
+pub fn parse_node_line(line: &str) -> Option<(isize, Point)> {
+    let cap = OSM_NODE_RE.captures(line)?;
+    match (cap[1].parse::<isize>(), cap[2].parse::<f32>(), cap[3].parse::<f32>()) {
+        (Ok(osm_id), Ok(lat), Ok(lon)) => Some((osm_id, Point{lat, lon})),
+        _ => None,
+    }
+}
+
+// Seconds to cover `distance` meters at `speed_factor` m/s. The only
+// rounding step: searches just add up these integers (saturating). `as`
+// saturates, the `min` keeps clear of `usize::MAX`. Distinct nodes mapped
+// onto each other mustn't be free to cross, so zero distance costs 1.
+pub(crate) fn arc_cost(distance: f32, speed_factor: f32) -> usize {
+    if distance == 0_f32 {
+        return 1;
+    }
+    ((distance / speed_factor) as usize).min(MAX_ARC_COST)
+}
+
+// "MM", "HH:MM" or "HH:MM:SS" (the `duration` tag) to seconds.
+fn parse_duration(duration: &str) -> Option<u32> {
+    let duration = duration.trim();
+    if !duration.contains(':') {
+        return duration.parse::<u32>().ok()?.checked_mul(60);
+    }
+    let mut parts = duration.split(':').map(|part| part.parse::<u32>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?);
+    if parts.next().is_some() || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    hours.checked_mul(3600)?.checked_add(minutes * 60 + seconds)
+}
+
+// Resolves the predefined XML entities and character references (`&amp;`,
+// `&#223;`, `&#xDF;`) in an attribute value. Anything else that starts with
+// `&` is kept as it is.
+fn decode_xml_entities(value: &str) -> Cow<'_, str> {
+    if !value.contains('&') {
+        return Cow::Borrowed(value);
+    }
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|decimal| decimal.parse::<u32>().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+// Parses a `<tag k=".." v=".."/>` line into its key and value, with XML
+// entities resolved; `None` for any other line.
+pub fn parse_tag_line(line: &str) -> Option<(String, String)> {
+    let cap = OSM_TAG_RE.captures(line)?;
+    Some((decode_xml_entities(&cap[1]).into_owned(), decode_xml_entities(&cap[2]).into_owned()))
+}
+
+// Handles one line inside a `<way>` element, returns true on `</way>`. Of
+// repeated keys, the last value is kept.
+fn parse_way_line(line: &str, hops: &mut Vec<isize>, tags: &mut HashMap<String, String>) -> bool {
+    if let Some(cap) = OSM_ND_RE.captures(line) {
+        if let Ok(hop) = cap[1].parse::<isize>() {
+            hops.push(hop);
+        }
+    } else if let Some((key, value)) = parse_tag_line(line) {
+        tags.insert(key, value);
+    } else if line.starts_with(r"</way") {
+        return true;
+    }
+    false
+}
+
+// Parses a complete `<way>` element written one child per line (as osmium and
+// osmosis do) into its node refs and relevant tags. `None` if it isn't closed.
+pub fn parse_way(element: &str) -> Option<(Vec<isize>, WayTags)> {
+    let mut lines = element.lines().map(str::trim_start);
+    if !lines.next()?.starts_with(r"<way ") {
+        return None;
+    }
+    let mut hops = Vec::new();
+    let mut tags = HashMap::new();
+    for line in lines {
+        if parse_way_line(line, &mut hops, &mut tags) {
+            return Some((hops, WayTags::from_tags(&tags)));
+        }
+    }
+    None
+}
+
+impl Default for RoadNetwork {
+    fn default() -> RoadNetwork {
+        RoadNetwork::new()
+    }
+}
+
+// Collects nodes only. Arcs need the coordinates of both ends, so they are
+// added in a second step, e.g. `nodes.into_iter().collect::<RoadNetwork>().with_arcs(arcs)`.
+impl FromIterator<(isize, Point)> for RoadNetwork {
+    fn from_iter<I: IntoIterator<Item = (isize, Point)>>(nodes: I) -> RoadNetwork {
+        let mut network = RoadNetwork::new();
+        network.add_nodes(nodes);
+        network
+    }
+}
+
+impl Extend<(isize, Point)> for RoadNetwork {
+    fn extend<I: IntoIterator<Item = (isize, Point)>>(&mut self, nodes: I) {
+        self.add_nodes(nodes);
+    }
+}
+
+// "lat, lon", the format `Point::from_str` reads. A precision applies to
+// both numbers: `format!("{:.3}", point)` gives "49.234, 7.012".
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*}, {:.*}", precision, self.lat, precision, self.lon),
+            None => write!(f, "{}, {}", self.lat, self.lon),
+        }
+    }
+}
+
+// "-> node[12] 34s", with the internal index of the head node.
+impl fmt::Display for Arc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "-> node[{}] {}s", self.index, self.cost)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePointError {
+    MissingComma,
+    InvalidNumber(String),  // the part that isn't a finite number
+    OutOfRange,  // latitude beyond ±90 or longitude beyond ±180 degrees
+}
+
+impl fmt::Display for ParsePointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParsePointError::MissingComma => write!(f, "expected \"lat,lon\""),
+            ParsePointError::InvalidNumber(part) => write!(f, "`{}` is not a number", part),
+            ParsePointError::OutOfRange => write!(f, "coordinates out of range"),
+        }
+    }
+}
+
+impl Error for ParsePointError {}
+
+// "lat,lon" in degrees, e.g. "49.23,7.0"; spaces around the numbers are fine.
+impl FromStr for Point {
+    type Err = ParsePointError;
+
+    fn from_str(value: &str) -> Result<Point, ParsePointError> {
+        let (lat, lon) = value.split_once(',').ok_or(ParsePointError::MissingComma)?;
+        let parse = |part: &str| part.trim().parse::<f32>().ok()
+            .filter(|number| number.is_finite())
+            .ok_or_else(|| ParsePointError::InvalidNumber(part.trim().to_string()));
+        let (lat, lon) = (parse(lat)?, parse(lon)?);
+        if lat.abs() > 90_f32 || lon.abs() > 180_f32 {
+            return Err(ParsePointError::OutOfRange);
+        }
+        Ok(Point {lat, lon})
+    }
+}
+
+impl Sub for Point {
+    type Output = f32;
+
+    fn sub(self, other: Point) -> f32 {
+        (
+            ((self.lat - other.lat) * 111_229_f32).powi(2) +
+            ((self.lon - other.lon) * 71_695_f32).powi(2)
+        ).sqrt()
+    }
+}
+
+impl Point {
+    // Linear interpolation, `t = 0` gives `self` and `t = 1` gives `other`.
+    pub fn interpolate(self, other: Point, t: f32) -> Point {
+        Point {
+            lat: self.lat + (other.lat - self.lat) * t,
+            lon: self.lon + (other.lon - self.lon) * t,
+        }
+    }
+
+    // Initial compass bearing from `self` towards `other` in degrees (0 = north, 90 = east).
+    pub fn bearing(self, other: Point) -> f32 {
+        let (lat_a, lat_b) = (self.lat.to_radians(), other.lat.to_radians());
+        let delta_lon = (other.lon - self.lon).to_radians();
+        let y = delta_lon.sin() * lat_b.cos();
+        let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * delta_lon.cos();
+        (y.atan2(x).to_degrees() + 360_f32) % 360_f32
+    }
+}
+
+impl RoadNetwork {
+    pub fn new() -> RoadNetwork {
+        RoadNetwork {
+            osm_id_map: HashMap::new(),
+            osm_ids: Vec::new(),
+            nodes: HashMap::new(),
+            points: Vec::new(),
+            adjacent_arcs: Vec::new(),
+            barriers: HashMap::new(),
+            names: Interner::default(),
+            profile: SpeedProfile::default(),
+            missing_coord_policy: MissingCoordPolicy::default(),
+            max_arc_length: None,
+            highway_filter: HighwayFilter::default(),
+            parse_stats: ParseStats::default(),
+            source_metadata: None,
+            conditional_speeds: HashMap::new(),
+            dimension_limits: HashMap::new(),
+            distance_model: default_distance_model(),
+            speed_bound: sync::OnceLock::new(),
+            component_labels: None,
+            spatial_index: sync::OnceLock::new(),
+            arcs_per_node: 0,
+        }
+    }
+
+    // Empty network with room for `nodes` nodes and about `arcs` arcs
+    // (directed, so two per bidirectional road) before reallocating. Arcs
+    // live in one list per node, so they are reserved as the average number
+    // of arcs per node whenever a node gets its index.
+    pub fn with_capacity(nodes: usize, arcs: usize) -> RoadNetwork {
+        let mut network = RoadNetwork::new();
+        network.osm_id_map.reserve(nodes);
+        network.osm_ids.reserve(nodes);
+        network.nodes.reserve(nodes);
+        network.points.reserve(nodes);
+        network.adjacent_arcs.reserve(nodes);
+        network.arcs_per_node = arcs.div_ceil(nodes.max(1));
+        network
+    }
+
+    // Empty network with the same configuration as `self`.
+    pub(crate) fn _empty_like(&self) -> RoadNetwork {
+        let mut network = RoadNetwork::new();
+        network.names = self.names.clone();
+        network.profile = self.profile.clone();
+        network.missing_coord_policy = self.missing_coord_policy;
+        network.max_arc_length = self.max_arc_length;
+        network.highway_filter = self.highway_filter.clone();
+        network.distance_model = self.distance_model.clone();
+        network.source_metadata = self.source_metadata.clone();
+        network
+    }
+
+    // How arc lengths are derived from coordinates, `Haversine` by default.
+    pub fn with_distance_model(mut self, model: impl DistanceModel + 'static) -> RoadNetwork {
+        self.distance_model = sync::Arc::new(model);
+        self
+    }
+
+    // Speeds used for arcs parsed from now on.
+    pub fn with_speed_profile(mut self, profile: SpeedProfile) -> RoadNetwork {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_missing_coord_policy(mut self, policy: MissingCoordPolicy) -> RoadNetwork {
+        self.missing_coord_policy = policy;
+        self
+    }
+
+    // Drops arcs longer than `meters` (counted in `ParseStats`), as those are
+    // usually caused by broken coordinates. Arcs of non-finite length are
+    // always dropped.
+    pub fn with_max_arc_length(mut self, meters: f32) -> RoadNetwork {
+        self.max_arc_length = Some(meters);
+        self
+    }
+
+    // Only ways parsed from now on are filtered.
+    pub fn with_highway_filter(mut self, filter: HighwayFilter) -> RoadNetwork {
+        self.highway_filter = filter;
+        self
+    }
+
+    pub fn speed_profile(&self) -> &SpeedProfile {
+        &self.profile
+    }
+
+    pub fn parse_stats(&self) -> ParseStats {
+        self.parse_stats
+    }
+
+    // `None` unless an OSM XML file with an `<osm>` header was read.
+    pub fn source_metadata(&self) -> Option<&SourceMetadata> {
+        self.source_metadata.as_ref()
+    }
+
+    pub fn arc_cost_summary(&self) -> CostSummary {
+        let mut summary = CostSummary {min: usize::MAX, max: 0, mean: 0_f32, zero_cost: 0};
+        let mut count = 0;
+        let mut total: u64 = 0;
+        for arc in self.adjacent_arcs.iter().flatten() {
+            summary.min = summary.min.min(arc.cost);
+            summary.max = summary.max.max(arc.cost);
+            if arc.cost == 0 {
+                summary.zero_cost += 1;
+            }
+            total = total.saturating_add(arc.cost as u64);
+            count += 1;
+        }
+        if count == 0 {
+            summary.min = 0;
+        } else {
+            summary.mean = total as f32 / count as f32;
+        }
+        summary
+    }
+
+    // A node may also be added after ways referenced it (e.g. in hand-edited
+    // files): its slot in the graph is filled then, but the arcs the way
+    // would have had were already handled by the `MissingCoordPolicy`.
+    pub fn add_node(&mut self, osm_id: isize, location: Point) {
+        self.spatial_index.take();
+        self.nodes.insert(osm_id, location);
+        if let Some(index) = self.get_index(osm_id) {
+            self.points[index] = Some(location);
+        }
+    }
+
+    pub fn add_nodes(&mut self, nodes: impl IntoIterator<Item = (isize, Point)>) {
+        let nodes = nodes.into_iter();
+        self.nodes.reserve(nodes.size_hint().0);
+        for (osm_id, location) in nodes {
+            self.add_node(osm_id, location);
+        }
+    }
+
+    // Bidirectional arcs as `(osm_id_a, osm_id_b, speed_factor)`, see `add_arc`.
+    pub fn add_arcs(&mut self, arcs: impl IntoIterator<Item = (isize, isize, f32)>) {
+        let arcs = arcs.into_iter();
+        let additional = arcs.size_hint().0;
+        self.osm_id_map.reserve(additional);
+        self.osm_ids.reserve(additional);
+        self.points.reserve(additional);
+        self.adjacent_arcs.reserve(additional);
+        for (osm_id_a, osm_id_b, speed_factor) in arcs {
+            self.add_arc(osm_id_a, osm_id_b, speed_factor);
+        }
+    }
+
+    // Builder version of `add_arcs`, for networks collected from nodes.
+    pub fn with_arcs(mut self, arcs: impl IntoIterator<Item = (isize, isize, f32)>) -> RoadNetwork {
+        self.add_arcs(arcs);
+        self
+    }
+
+    // Imports all nodes and arcs of `other`. Nodes present in both networks
+    // share one index; if their coordinates differ, the ones already in
+    // `self` are kept (existing arc costs were computed from them).
+    pub fn merge(&mut self, other: RoadNetwork) {
+        // through `add_node`, so the points by index stay in sync
+        for (osm_id, location) in other.nodes {
+            if !self.nodes.contains_key(&osm_id) {
+                self.add_node(osm_id, location);
+            }
+        }
+        for (osm_id, barrier) in other.barriers {
+            self.barriers.entry(osm_id).or_insert(barrier);
+        }
+        self.parse_stats.duplicate_refs += other.parse_stats.duplicate_refs;
+        self.parse_stats.rejected_long_arcs += other.parse_stats.rejected_long_arcs;
+        self.parse_stats.unparsed_conditions += other.parse_stats.unparsed_conditions;
+        self.parse_stats.invalid_utf8_lines += other.parse_stats.invalid_utf8_lines;
+        self.parse_stats.self_loops += other.parse_stats.self_loops;
+        self.parse_stats.zero_length_arcs += other.parse_stats.zero_length_arcs;
+        self.parse_stats.filtered_ways += other.parse_stats.filtered_ways;
+        if self.source_metadata.is_none() {
+            self.source_metadata = other.source_metadata;
+        }
+        let indices: Vec<usize> = other.osm_ids.iter()
+            .map(|&osm_id| self.get_or_create_index(osm_id))
+            .collect();
+        for (way, speeds) in other.conditional_speeds {
+            self.conditional_speeds.entry(way).or_insert(speeds);
+        }
+        for (way, limits) in other.dimension_limits {
+            self.dimension_limits.entry(way).or_insert(limits);
+        }
+        let other_names = other.names;
+        for (other_index, arcs) in other.adjacent_arcs.into_iter().enumerate() {
+            for mut arc in arcs {
+                arc.index = indices[arc.index];
+                arc.name = arc.name
+                    .and_then(|name| other_names.get(name))
+                    .map(|name| self.names.intern(name));
+                arc.class = arc.class
+                    .and_then(|class| other_names.get(class))
+                    .map(|class| self.names.intern(class));
+                self._push_arc_at_index(indices[other_index], arc);
+            }
+        }
+    }
+
+    // Same nodes and the same arcs (by OSM ID, cost and name), regardless of
+    // index assignment and arc order. There's intentionally no `PartialEq`:
+    // a derived one would compare indices and adjacency order, which differ
+    // between networks built from the same data in a different order.
+    pub fn structurally_equal(&self, other: &RoadNetwork) -> bool {
+        let mut osm_ids = self.osm_ids.clone();
+        let mut other_osm_ids = other.osm_ids.clone();
+        osm_ids.sort_unstable();
+        other_osm_ids.sort_unstable();
+        self.nodes == other.nodes
+            && osm_ids == other_osm_ids
+            && self._sorted_arcs() == other._sorted_arcs()
+    }
+
+    // All arcs as `(from, to, cost, name)`, sorted.
+    fn _sorted_arcs(&self) -> Vec<(isize, isize, usize, Option<&str>)> {
+        let mut arcs: Vec<_> = self.adjacent_arcs.iter().enumerate()
+            .flat_map(|(index, arcs)| arcs.iter().map(move |arc| (index, arc)))
+            .map(|(index, arc)| (
+                self.osm_ids[index], self.osm_ids[arc.index], arc.cost, self.arc_name(arc)
+            ))
+            .collect();
+        arcs.sort_unstable();
+        arcs
+    }
+
+    pub fn get_index(&self, osm_id: isize) -> Option<usize> {
+        self.osm_id_map.get(&osm_id).copied()
+    }
+
+    // Nodes that are part of the road graph (not all parsed nodes).
+    pub fn node_count(&self) -> usize {
+        self.adjacent_arcs.len()
+    }
+
+    pub fn arc_count(&self) -> usize {
+        self.adjacent_arcs.iter().map(Vec::len).sum()
+    }
+
+    // Number of outgoing arcs of a node, `None` for nodes not in the graph.
+    pub fn degree(&self, osm_id: isize) -> Option<usize> {
+        self.get_index(osm_id).map(|index| self.adjacent_arcs[index].len())
+    }
+
+    // At least three outgoing arcs. Counts arcs rather than distinct
+    // neighbors, and a junction of one-way roads may fall below three.
+    pub fn is_intersection(&self, osm_id: isize) -> bool {
+        self.degree(osm_id).is_some_and(|degree| degree >= 3)
+    }
+
+    // Value of the node's `barrier` tag, if it has one.
+    pub fn barrier(&self, osm_id: isize) -> Option<&str> {
+        self.barriers.get(&osm_id).map(String::as_str)
+    }
+
+    pub fn get_osm_id(&self, index: usize) -> Option<isize> {
+        self.osm_ids.get(index).copied()
+    }
+
+    pub fn adjacent_arcs(&self) -> &[Vec<Arc>] {
+        &self.adjacent_arcs
+    }
+
+    // Name of the way the arc was created from, if it had one.
+    pub fn arc_name(&self, arc: &Arc) -> Option<&str> {
+        arc.name.and_then(|name| self.names.get(name))
+    }
+
+    // Highway class of the way the arc was created from, `None` for arcs
+    // added through `add_arc`.
+    pub fn arc_class(&self, arc: &Arc) -> Option<&str> {
+        arc.class.and_then(|class| self.names.get(class))
+    }
+
+    pub fn get_or_create_index(&mut self, osm_id: isize) -> usize {
+        match self.get_index(osm_id) {
+            Some(index) => index,
+            None => {
+                self.component_labels = None;
+                self.spatial_index.take();
+                let index = self.adjacent_arcs.len();
+                self.adjacent_arcs.push(Vec::with_capacity(self.arcs_per_node));
+                self.osm_id_map.insert(osm_id, index);
+                self.osm_ids.push(osm_id);
+                self.points.push(self.nodes.get(&osm_id).copied());
+                index
+            }
+        }
+    }
+
+    // Coordinates of a graph node by internal index (as in `Arc::index`).
+    pub fn point_by_index(&self, index: usize) -> Option<Point> {
+        self.points.get(index).copied().flatten()
+    }
+
+    // Coordinates of any parsed node, whether it is in the graph or not.
+    pub fn point_by_osm_id(&self, osm_id: isize) -> Option<Point> {
+        self.nodes.get(&osm_id).copied()
+    }
+
+    pub fn distance(&self, osm_id_a: isize, osm_id_b: isize) -> f32 {
+        let location_a = self.point_by_osm_id(osm_id_a).unwrap();
+        let location_b = self.point_by_osm_id(osm_id_b).unwrap();
+        self.distance_model.distance(location_a, location_b)
+    }
+
+    // What the arc `osm_id_a -> osm_id_b` would cost at `speed_kmh`, for
+    // what-if comparisons with its actual cost. Uses the arc's stored length
+    // and rounds like when building arcs (penalties, caps and conditions are
+    // not applied). `None` if there is no such arc, it has no length (a node
+    // lacks coordinates) or the speed isn't positive.
+    pub fn arc_cost_at_speed(&self, osm_id_a: isize, osm_id_b: isize, speed_kmh: f32) -> Option<usize> {
+        let (index_a, index_b) = (self.get_index(osm_id_a)?, self.get_index(osm_id_b)?);
+        let arc = self.adjacent_arcs[index_a].iter().find(|arc| arc.index == index_b)?;
+        if !(speed_kmh.is_finite() && speed_kmh > 0_f32) {
+            return None;
+        }
+        Some(arc_cost(arc.length?, speed_kmh * KMPH))
+    }
+
+    fn _nearest_node(&self, location: Point) -> Option<(isize, f32)> {
+        self._nearest(location, 1, |_| true).pop()
+    }
+
+    // Up to `count` parsed nodes accepted by `filter` closest to `location`
+    // with their distances, nearest first (ties go to the lower OSM ID). Only
+    // looks at the nodes around `location` (see `_spatial_index`).
+    pub(crate) fn _nearest(
+        &self, location: Point, count: usize, filter: impl Fn(&spatial::Entry) -> bool
+    ) -> Vec<(isize, f32)> {
+        self._spatial_index().nearest(location, count, self.distance_model.as_ref(), filter)
+    }
+
+    pub fn nearest_node(&self, location: Point) -> Option<isize> {
+        self._nearest_node(location).map(|(osm_id, _)| osm_id)
+    }
+
+    // Like `nearest_node`, but only considers nodes with at least one arc to
+    // or from them, skipping nodes that are only part of ways which weren't
+    // turned into arcs (e.g. blocked highway classes, or whose only segments
+    // were rejected).
+    pub fn nearest_routable_node(&self, location: Point) -> Option<isize> {
+        self._nearest(location, 1, |entry| entry.routable).pop().map(|(osm_id, _)| osm_id)
+    }
+
+    // Like `nearest_node`, but gives up if the closest node is more than
+    // `max_meters` away (e.g. for GPS points outside of the parsed extract).
+    pub fn nearest_node_within(&self, location: Point, max_meters: f32) -> Option<isize> {
+        match self._nearest_node(location) {
+            Some((osm_id, distance)) if distance <= max_meters => Some(osm_id),
+            _ => None,
+        }
+    }
+
+    // Road graph nodes inside the box spanned by the south-west corner `min`
+    // and the north-east corner `max` (inclusive), sorted by OSM ID. A box with
+    // `min.lon > max.lon` wraps around the antimeridian. Only looks at the
+    // nodes near the box (see `_spatial_index`).
+    pub fn nodes_in_bbox(&self, min: Point, max: Point) -> Vec<isize> {
+        let mut osm_ids = Vec::new();
+        self._spatial_index().visit_box(min, max, |entry| {
+            if entry.in_graph && bbox_contains(min, max, entry.point) {
+                osm_ids.push(entry.osm_id);
+            }
+        });
+        osm_ids.sort_unstable();
+        osm_ids
+    }
+
+    // Grid over all parsed nodes, built on first use and dropped whenever
+    // nodes or arcs are added.
+    fn _spatial_index(&self) -> &spatial::SpatialIndex {
+        self.spatial_index.get_or_init(|| {
+            let mut routable: Vec<bool> = self.adjacent_arcs.iter().map(|arcs| !arcs.is_empty()).collect();
+            for arc in self.adjacent_arcs.iter().flatten() {
+                routable[arc.index] = true;
+            }
+            spatial::SpatialIndex::new(self.nodes.iter().map(|(&osm_id, &point)| {
+                let index = self.get_index(osm_id);
+                spatial::Entry {osm_id, point, in_graph: index.is_some(), routable: index.is_some_and(|index| routable[index])}
+            }))
+        })
+    }
+
+    fn _push_arc_at_index(&mut self, index: usize, arc: Arc) {
+        self.speed_bound.take();
+        self.component_labels = None;
+        self.spatial_index.take();
+        let node = self.adjacent_arcs.get_mut(index).unwrap();
+        node.push(arc);
+    }
+
+    fn _add_arc(
+        &mut self, osm_id_a: isize, osm_id_b: isize, speed_factor: f32, bidirectional: bool, origin: ArcOrigin
+    ) -> Result<bool, OsmError> {
+        if osm_id_a == osm_id_b {
+            self.parse_stats.self_loops += 1;
+            return Ok(false);
+        }
+        // ways may reference nodes outside of the extract
+        let missing = [osm_id_a, osm_id_b].iter().copied().find(|osm_id| !self.nodes.contains_key(osm_id));
+        let (cost, length) = match (missing, self.missing_coord_policy) {
+            (None, _) => {
+                let distance = self.distance(osm_id_a, osm_id_b);
+                if !distance.is_finite() || self.max_arc_length.is_some_and(|max| distance > max) {
+                    self.parse_stats.rejected_long_arcs += 1;
+                    return Ok(false);
+                }
+                if distance == 0_f32 {
+                    self.parse_stats.zero_length_arcs += 1;
+                }
+                (arc_cost(distance, speed_factor), Some(distance))
+            }
+            (Some(_), MissingCoordPolicy::Skip) => return Ok(false),
+            (Some(_), MissingCoordPolicy::ZeroCost) => (0, None),
+            (Some(osm_id), MissingCoordPolicy::Error) => return Err(OsmError::MissingCoordinates(osm_id)),
+        };
+        let index_a = self.get_or_create_index(osm_id_a);
+        let index_b = self.get_or_create_index(osm_id_b);
+        let ArcOrigin {name, class, way} = origin;
+        self._push_arc_at_index(index_a, Arc {index: index_b, cost, name, class, way, length});
+        if bidirectional {
+            self._push_arc_at_index(index_b, Arc {index: index_a, cost, name, class, way, length});
+        }
+        Ok(true)
+    }
+
+    // `speed_factor` is in m/s, limited by the profile's `global_speed_cap_kmh`.
+    // Nodes without coordinates are handled as with `MissingCoordPolicy::Skip`
+    // under the `Error` policy.
+    pub fn add_arc(&mut self, osm_id_a: isize, osm_id_b: isize, speed_factor: f32) {
+        let speed_factor = self.profile.capped_kmh(speed_factor / KMPH) * KMPH;
+        let _ = self._add_arc(osm_id_a, osm_id_b, speed_factor, true, ArcOrigin::default());
+    }
+
+    // Only allows travelling from `osm_id_a` to `osm_id_b`.
+    pub fn add_oneway_arc(&mut self, osm_id_a: isize, osm_id_b: isize, speed_factor: f32) {
+        let speed_factor = self.profile.capped_kmh(speed_factor / KMPH) * KMPH;
+        let _ = self._add_arc(osm_id_a, osm_id_b, speed_factor, false, ArcOrigin::default());
+    }
+
+    // Switches to another profile without parsing again: every arc created
+    // from a way gets its cost from its length and the new profile's speed
+    // for its class, arcs of classes the profile doesn't route on are
+    // removed. Only the class is looked at, so what else the parsing profile
+    // decided stays: `maxspeed`, `lanes`, penalties and ferry durations
+    // aren't applied again, one-way roads keep their direction and barriers
+    // their arcs. Arcs added through `add_arc` keep their cost. Time
+    // windows from `maxspeed:conditional` are dropped along with `maxspeed`,
+    // so `shortest_path_departing_at` routes like `shortest_path`. Classes
+    // the parsing profile skipped aren't in the graph, so parse with a
+    // `default_unknown_speed` to be able to switch to any profile later.
+    pub fn recompute_costs(&mut self, profile: &SpeedProfile) {
+        let mut speed_factors: HashMap<usize, Option<f32>> = HashMap::new();
+        let names = &self.names;
+        for arcs in &mut self.adjacent_arcs {
+            arcs.retain_mut(|arc| {
+                let Some(class) = arc.class else { return true };
+                let speed_factor = *speed_factors.entry(class).or_insert_with(|| {
+                    let speed = match names.get(class) {
+                        Some("ferry") => profile.ferry_speed_kmh,
+                        Some(class) => profile.speed_kmh(class),
+                        None => None,
+                    };
+                    speed.map(|speed| KMPH * profile.capped_kmh(speed))
+                });
+                let Some(speed_factor) = speed_factor else { return false };
+                if let Some(length) = arc.length {
+                    arc.cost = arc_cost(length, speed_factor);
+                }
+                true
+            });
+        }
+        self.conditional_speeds.clear();
+        self.profile = profile.clone();
+        self.speed_bound.take();
+        self.component_labels = None;
+    }
+
+    pub fn read_from_osm_file(&mut self, filename: &str) -> std::io::Result<()>{
+        let file = File::open(filename)?;
+        self.read_from_reader(BufReader::new(file))
+    }
+
+    // Maps the whole file into memory instead of reading it through a buffer,
+    // which is faster on multi-gigabyte extracts. Produces the same network.
+    #[cfg(feature = "mmap")]
+    pub fn read_from_osm_file_mmap(&mut self, filename: &str) -> std::io::Result<()>{
+        let file = File::open(filename)?;
+        // Safety: the map is only read while parsing; like any mmap this
+        // assumes no other process truncates the file in the meantime.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        self.read_from_reader(&map[..])
+    }
+
+    // Only keeps the nodes inside the box spanned by `min` (south-west) and
+    // `max` (north-east), see `nodes_in_bbox`, so memory use depends on the
+    // box rather than the file. Ways crossing the boundary are clipped: only
+    // segments with both nodes inside become arcs, a way leaving and
+    // re-entering the box is split into its inside parts. Nodes without
+    // coordinates count as outside, regardless of `MissingCoordPolicy`.
+    pub fn read_from_osm_file_in_bbox(&mut self, filename: &str, min: Point, max: Point) -> std::io::Result<()>{
+        let file = File::open(filename)?;
+        self._read_from_reader(BufReader::new(file), Some((min, max)), ParseProgress::default(), |_| {}, |_, _| Ok(()))
+    }
+
+    pub fn read_from_reader<R: BufRead>(&mut self, reader: R) -> std::io::Result<()>{
+        self.read_from_reader_with_progress(reader, |_| {})
+    }
+
+    // Like `read_from_reader`, calling `on_progress` every `PROGRESS_INTERVAL`
+    // lines and once at the end.
+    pub fn read_from_reader_with_progress<R: BufRead>(
+        &mut self, reader: R, on_progress: impl FnMut(ParseProgress)
+    ) -> std::io::Result<()>{
+        self._read_from_reader(reader, None, ParseProgress::default(), on_progress, |_, _| Ok(()))
+    }
+
+    // The line parser behind all XML readers. Starts counting at `progress`
+    // (the reader is expected to be positioned at `progress.bytes_read`) and
+    // calls `at_boundary` before every line that begins a new element, where
+    // the network alone describes everything parsed so far.
+    pub(crate) fn _read_from_reader<R: BufRead>(
+        &mut self, mut reader: R, bbox: Option<(Point, Point)>, mut progress: ParseProgress,
+        mut on_progress: impl FnMut(ParseProgress),
+        mut at_boundary: impl FnMut(&RoadNetwork, ParseProgress) -> std::io::Result<()>
+    ) -> std::io::Result<()>{
+        let mut hops: Vec<isize> = Vec::new();
+        let mut tags: HashMap<String, String> = HashMap::new();  // of the open node or way
+        let mut is_way = false;
+        let mut way_id = None;
+        let mut open_node = None;  // a `<node>` with child tags
+        let mut in_comment = false;  // inside a `<!-- -->` spanning lines
+        let mut buffer = Vec::new();
+
+        for line_number in 0.. {
+            if !is_way && open_node.is_none() && !in_comment {
+                at_boundary(self, progress)?;
+            }
+            if line_number % PROGRESS_INTERVAL == PROGRESS_INTERVAL - 1 {
+                on_progress(progress);
+            }
+            buffer.clear();
+            let read = reader.read_until(b'\n', &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            progress.bytes_read += read as u64;
+            // invalid bytes only spoil the value they're in (usually a name),
+            // the rest of the line is still parsed
+            let decoded = String::from_utf8_lossy(&buffer);
+            if let Cow::Owned(_) = decoded {
+                self.parse_stats.invalid_utf8_lines += 1;
+            }
+            let line: &str = &decoded;
+            let line = line.strip_suffix('\n').map_or(line, |line| line.strip_suffix('\r').unwrap_or(line));
+            let line = strip_xml_comments(line, &mut in_comment);
+            let trimmed_line = line.trim_start();
+            if trimmed_line.starts_with("<osm ") || trimmed_line.starts_with("<osm>") {
+                if self.source_metadata.is_none() {
+                    let attribute = |re: &Regex| re.captures(trimmed_line)
+                        .map(|cap| decode_xml_entities(&cap[1]).into_owned());
+                    self.source_metadata = Some(SourceMetadata {
+                        version: attribute(&OSM_VERSION_RE),
+                        generator: attribute(&OSM_GENERATOR_RE),
+                    });
+                }
+            } else if let Some((osm_id, location)) = parse_node_line(trimmed_line) {
+                progress.nodes += 1;
+                if bbox.is_some_and(|(min, max)| !bbox_contains(min, max, location)) {
+                    continue;
+                }
+                self.add_node(osm_id, location);
+                let line_end = trimmed_line.trim_end();
+                if line_end.ends_with("</node>") {
+                    // opened and closed on one line, maybe with tags in between
+                    let node_tags: HashMap<String, String> = OSM_TAG_RE.captures_iter(trimmed_line)
+                        .map(|cap| (decode_xml_entities(&cap[1]).into_owned(), decode_xml_entities(&cap[2]).into_owned()))
+                        .collect();
+                    self._add_node_tags(osm_id, &node_tags);
+                } else if !line_end.ends_with("/>") {
+                    open_node = Some(osm_id);
+                    tags.clear();
+                }
+            } else if let Some(osm_id) = open_node {
+                if let Some((key, value)) = parse_tag_line(trimmed_line) {
+                    tags.insert(key, value);
+                } else if trimmed_line.starts_with(r"</node") {
+                    self._add_node_tags(osm_id, &tags);
+                    open_node = None;
+                }
+            } else if trimmed_line.starts_with(r"<way ") {
+                hops = Vec::new();
+                tags.clear();
+                way_id = OSM_WAY_ID_RE.captures(trimmed_line).and_then(|cap| cap[1].parse::<isize>().ok());
+                is_way = true;
+            } else if is_way && parse_way_line(trimmed_line, &mut hops, &mut tags) {
+                let tags = WayTags::from_tags(&tags);
+                if bbox.is_some() {
+                    let runs: Vec<&[isize]> = hops.split(|hop| !self.nodes.contains_key(hop))
+                        .filter(|run| run.len() > 1)
+                        .collect();
+                    for inside in runs {
+                        self._add_way(inside, &tags, way_id)?;
+                    }
+                } else {
+                    self._add_way(&hops, &tags, way_id)?;
+                }
+                is_way = false;
+                progress.ways += 1;
+            }
+        }
+        on_progress(progress);
+        Ok(())
+    }
+
+    // Keeps what matters of a node's tags. Shared by all input formats.
+    pub(crate) fn _add_node_tags(&mut self, osm_id: isize, tags: &HashMap<String, String>) {
+        if let Some(barrier) = tags.get("barrier") {
+            self.barriers.insert(osm_id, barrier.clone());
+        }
+    }
+
+    fn _is_blocking_barrier(&self, osm_id: isize) -> bool {
+        self.barriers.get(&osm_id).is_some_and(|barrier| self.profile.blocks_barrier(barrier))
+    }
+
+    // Length in meters of the way's segments between nodes with coordinates.
+    fn _way_length(&self, hops: &[isize]) -> f32 {
+        hops.windows(2)
+            .filter(|pair| self.nodes.contains_key(&pair[0]) && self.nodes.contains_key(&pair[1]))
+            .map(|pair| self.distance(pair[0], pair[1]))
+            .sum()
+    }
+
+    // Turns a parsed way into arcs between consecutive nodes, if its tags make
+    // it routable. Shared by all input formats.
+    pub(crate) fn _add_way(&mut self, hops: &[isize], tags: &WayTags, way: Option<isize>) -> Result<(), OsmError> {
+        let is_ferry = tags.highway.is_none() && tags.route.as_deref() == Some("ferry");
+        let class = if is_ferry { Some("ferry") } else { tags.highway.as_deref() };
+        if class.is_some_and(|class| !self.highway_filter.allows(class)) {
+            self.parse_stats.filtered_ways += 1;
+            return Ok(());
+        }
+        let class_speed = if is_ferry {
+            self.profile.ferry_speed_kmh
+        } else {
+            tags.highway.as_deref().and_then(|class| self.profile.speed_kmh(class))
+        };
+        let Some(class_speed) = class_speed else {
+            return Ok(());
+        };
+        // a usable maxspeed tag overrides the class default
+        let speed = tags.maxspeed.as_deref()
+            .filter(|_| !is_ferry)
+            .and_then(|maxspeed| self.profile.maxspeed_kmh(maxspeed))
+            .unwrap_or(class_speed);
+        let lanes_factor = tags.lanes.as_deref().map_or(1_f32, |lanes| self.profile.lanes_factor(lanes));
+        let speed = self.profile.capped_kmh(speed * lanes_factor);
+        // penalties inflate the cost just like a lower speed would
+        let penalty = self.profile.penalty(tags);
+        let mut speed_factor = KMPH * speed / penalty;
+        // a ferry's duration is spread over its segments by length
+        if let Some(seconds) = tags.duration.as_deref().filter(|_| is_ferry).and_then(parse_duration) {
+            let length = self._way_length(hops);
+            if length > 0_f32 && seconds > 0 {
+                speed_factor = length / seconds as f32 / penalty;
+            }
+        }
+        let conditions = tags.maxspeed_conditional.as_deref().and_then(|value| {
+            let conditions = conditional::parse_maxspeed_conditional(value, &self.profile);
+            if conditions.is_none() {
+                self.parse_stats.unparsed_conditions += 1;
+            }
+            conditions
+        });
+        let conditional_speeds = conditions.map(|conditions| self._conditional_speeds(penalty, &conditions));
+        let oneway = match tags.oneway.as_deref().filter(|_| !self.profile.ignore_oneway) {
+            Some("yes") | Some("true") | Some("1") => Some(1),
+            Some("-1") | Some("reverse") => Some(-1),
+            Some("no") | Some("false") | Some("0") => Some(0),
+            _ => None,
+        };
+        // roundabouts are implicitly one-way unless tagged otherwise
+        let is_roundabout = matches!(tags.junction.as_deref(), Some("roundabout") | Some("circular"));
+        let direction = oneway.unwrap_or(if is_roundabout && !self.profile.ignore_oneway { 1 } else { 0 });
+        let name = tags.name.as_deref().map(|name| self.names.intern(name));
+        let class = class.map(|class| self.names.intern(class));
+        let origin = ArcOrigin {name, class, way};
+        let limits = Dimensions::from_tags(tags);
+        for pair in hops.windows(2) {
+            let (previous, hop) = (pair[0], pair[1]);
+            if previous == hop {
+                self.parse_stats.duplicate_refs += 1;
+                continue;
+            }
+            // no arcs into or out of nodes the profile can't pass
+            if self._is_blocking_barrier(previous) || self._is_blocking_barrier(hop) {
+                continue;
+            }
+            let (tail, head) = if direction == -1 { (hop, previous) } else { (previous, hop) };
+            let bidirectional = direction == 0;
+            let added = self._add_arc(tail, head, speed_factor, bidirectional, origin)?;
+            // parallel ways between the same nodes keep their own limits
+            if let (true, Some(way), Some(limits)) = (added, way, limits) {
+                self.dimension_limits.insert(way, limits);
+            }
+            if let (true, Some(way), Some(speeds)) = (added, way, &conditional_speeds) {
+                self.conditional_speeds.insert(way, speeds.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_cost_summary_of_known_costs() {
+        let mut network = RoadNetwork::new().with_distance_model(Euclidean);
+        network.add_nodes([(1, Point {lat: 0_f32, lon: 0_f32}), (2, Point {lat: 0_f32, lon: 30_f32}), (3, Point {lat: 0_f32, lon: 100_f32}), (4, Point {lat: 0_f32, lon: 100.5})]);
+        network.add_arcs([(1, 2, 10_f32), (2, 3, 10_f32), (3, 4, 10_f32)]);
+        // 3 s and 7 s in both directions, 0.05 s truncated to 0 s between 3 and 4
+        assert_eq!(network.arc_cost_summary(), CostSummary {min: 0, max: 7, mean: 10_f32 / 3_f32, zero_cost: 2});
+    }
+
+    #[test]
+    fn arc_cost_summary_of_empty_network() {
+        assert_eq!(RoadNetwork::new().arc_cost_summary(), CostSummary {min: 0, max: 0, mean: 0_f32, zero_cost: 0});
+    }
+
+    #[test]
+    fn nearest_node_within_radius() {
+        let network = fixtures::grid(3);
+        // about 50 m north of node 5 in the middle of the grid
+        let near = Point {lat: 49.00145, lon: 7.001};
+        assert_eq!(network.nearest_node_within(near, 100_f32), Some(5));
+        assert_eq!(network.nearest_node_within(near, 10_f32), None);
+        let far = Point {lat: 50_f32, lon: 7_f32};
+        assert_eq!(network.nearest_node_within(far, 1000_f32), None);
+        assert_eq!(network.nearest_node(far), Some(7));
+    }
+
+    const TRIANGLE: [(isize, f32, f32); 3] = [(1, 49_f32, 7_f32), (2, 49.001, 7.001), (3, 49_f32, 7.002)];
+
+    #[test]
+    fn roundabouts_are_one_way() {
+        let tags = [("highway", "primary"), ("junction", "roundabout")];
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3, 1], &tags)]));
+        assert!(fixtures::has_arc(&network, 1, 2) && fixtures::has_arc(&network, 2, 3) && fixtures::has_arc(&network, 3, 1));
+        assert!(!fixtures::has_arc(&network, 2, 1) && !fixtures::has_arc(&network, 3, 2) && !fixtures::has_arc(&network, 1, 3));
+        assert_eq!(network.adjacent_arcs().iter().map(Vec::len).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn explicit_oneway_wins_over_roundabout() {
+        let tags = [("highway", "primary"), ("junction", "circular"), ("oneway", "no")];
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3, 1], &tags)]));
+        assert_eq!(network.adjacent_arcs().iter().map(Vec::len).sum::<usize>(), 6);
+        let tags = [("highway", "primary"), ("junction", "roundabout"), ("oneway", "-1")];
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3, 1], &tags)]));
+        assert!(fixtures::has_arc(&network, 2, 1) && !fixtures::has_arc(&network, 1, 2));
+    }
+
+    #[test]
+    fn interpolate_midpoint() {
+        let (a, b) = (Point {lat: 48_f32, lon: 7_f32}, Point {lat: 49_f32, lon: 8_f32});
+        assert_eq!(a.interpolate(b, 0.5), Point {lat: 48.5, lon: 7.5});
+        assert_eq!(a.interpolate(b, 0_f32), a);
+        assert_eq!(a.interpolate(b, 1_f32), b);
+    }
+
+    #[test]
+    fn bearing_north_and_east() {
+        let origin = Point {lat: 0_f32, lon: 0_f32};
+        assert!(origin.bearing(Point {lat: 1_f32, lon: 0_f32}).abs() < 1e-3);
+        assert!((origin.bearing(Point {lat: 0_f32, lon: 1_f32}) - 90_f32).abs() < 1e-3);
+        assert!((origin.bearing(Point {lat: -1_f32, lon: 0_f32}) - 180_f32).abs() < 1e-3);
+        assert!((origin.bearing(Point {lat: 0_f32, lon: -1_f32}) - 270_f32).abs() < 1e-3);
+    }
+
+    #[test]
+    fn arcs_carry_the_interned_way_name() {
+        let xml = fixtures::osm_xml(&TRIANGLE, &[
+            (10, &[1, 2], &[("highway", "residential"), ("name", "Hauptstraße")]),
+            (11, &[2, 3], &[("highway", "residential"), ("name", "Hauptstraße")]),
+            (12, &[3, 1], &[("highway", "residential")]),
+        ]);
+        let network = fixtures::parse(&xml);
+        let mut names: Vec<(isize, isize, Option<&str>)> = network.adjacent_arcs().iter().enumerate()
+            .flat_map(|(index, arcs)| arcs.iter().map(move |arc| (index, arc)))
+            .map(|(index, arc)| (network.osm_ids[index], network.osm_ids[arc.index], network.arc_name(arc)))
+            .filter(|&(tail, head, _)| tail < head)
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, vec![(1, 2, Some("Hauptstraße")), (1, 3, None), (2, 3, Some("Hauptstraße"))]);
+        // both ways share one interned name
+        let ids: Vec<Option<usize>> = network.adjacent_arcs()[network.osm_id_map[&2]].iter().map(|arc| arc.name).collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], ids[1]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_parses_like_buffered_reading() {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm");
+        let mut buffered = RoadNetwork::new();
+        buffered.read_from_osm_file(filename).unwrap();
+        let mut mapped = RoadNetwork::new();
+        mapped.read_from_osm_file_mmap(filename).unwrap();
+        assert!(!buffered.adjacent_arcs().is_empty());
+        assert_eq!(mapped.osm_id_map, buffered.osm_id_map);
+        assert_eq!(mapped.nodes, buffered.nodes);
+        assert_eq!(format!("{:?}", mapped.adjacent_arcs()), format!("{:?}", buffered.adjacent_arcs()));
+    }
+
+    #[test]
+    fn batch_api_matches_single_inserts() {
+        let nodes: Vec<(isize, Point)> = TRIANGLE.iter().map(|&(osm_id, lat, lon)| (osm_id, Point {lat, lon})).collect();
+        let arcs = [(1, 2, 10_f32), (2, 3, 5_f32), (3, 1, 20_f32)];
+        let mut batched = RoadNetwork::new();
+        batched.add_nodes(nodes.iter().copied());
+        batched.add_arcs(arcs);
+        let mut single = RoadNetwork::new();
+        for &(osm_id, location) in &nodes {
+            single.add_node(osm_id, location);
+        }
+        for &(osm_id_a, osm_id_b, speed_factor) in &arcs {
+            single.add_arc(osm_id_a, osm_id_b, speed_factor);
+        }
+        assert_eq!(batched.adjacent_arcs().iter().map(Vec::len).sum::<usize>(), 6);
+        assert_eq!(batched.nodes, single.nodes);
+        assert_eq!(batched.osm_id_map, single.osm_id_map);
+        assert_eq!(format!("{:?}", batched.adjacent_arcs()), format!("{:?}", single.adjacent_arcs()));
+    }
+
+    #[test]
+    fn parse_single_node_lines() {
+        assert_eq!(
+            parse_node_line(r#"  <node id="-42" lat="48.5" lon="-7.25" version="2">"#),
+            Some((-42, Point {lat: 48.5, lon: -7.25}))
+        );
+        assert_eq!(parse_node_line(r#"<node id="1" lat="1.2.3" lon="7"/>"#), None);
+        assert_eq!(parse_node_line(r#"<node id="99999999999999999999" lat="1" lon="7"/>"#), None);
+        assert_eq!(parse_node_line(r#"<way id="1">"#), None);
+        assert_eq!(
+            parse_tag_line(r#"<tag k="name" v="Stra&#223;e &amp; Gasse"/>"#),
+            Some(("name".to_string(), "Straße & Gasse".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_single_way() {
+        let element = "<way id=\"10\">\n <nd ref=\"1\"/>\n <nd ref=\"-2\"/>\n <tag k=\"highway\" v=\"primary\"/>\n <tag k=\"oneway\" v=\"yes\"/>\n</way>";
+        let (hops, tags) = parse_way(element).unwrap();
+        assert_eq!(hops, vec![1, -2]);
+        assert_eq!((tags.highway.as_deref(), tags.oneway.as_deref()), (Some("primary"), Some("yes")));
+        assert_eq!(parse_way("<way id=\"10\">\n <nd ref=\"1\"/>"), None);
+        assert_eq!(parse_way("<node id=\"1\" lat=\"1\" lon=\"2\"/>"), None);
+    }
+
+    #[test]
+    fn merge_overlapping_networks() {
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        let node_3 = network.nodes[&3];
+        let mut other = RoadNetwork::new();
+        other.add_nodes([(3, Point {lat: 50_f32, lon: 8_f32}), (4, Point {lat: 49_f32, lon: 7.004})]);
+        other.add_oneway_arc(3, 4, 10_f32);
+        other.add_node(5, Point {lat: 49_f32, lon: 7.005});
+        other.add_arc(4, 5, 10_f32);
+        network.merge(other);
+        assert_eq!((network.node_count(), network.arc_count()), (5, 7));
+        // the coordinates already in `self` win
+        assert_eq!(network.nodes[&3], node_3);
+        assert_eq!(network.shortest_path(1, 5).map(|(_, path)| path), Some(vec![1, 2, 3, 4, 5]));
+        assert_eq!(network.shortest_path(5, 1), None);
+    }
+
+    #[test]
+    fn structural_equality_ignores_index_order() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, false), (3, 4, true)]);
+        let reordered = fixtures::network(&[(3, 4, true), (2, 3, false), (1, 2, true)]);
+        assert_ne!(network.get_index(1), reordered.get_index(1));
+        assert!(network.structurally_equal(&reordered));
+        let mut slower = fixtures::network(&[(1, 2, true), (2, 3, false)]);
+        slower.add_node(4, reordered.nodes[&4]);
+        slower.add_arc(3, 4, 5_f32);
+        assert!(!network.structurally_equal(&slower));
+        let reversed = fixtures::network(&[(1, 2, true), (3, 2, false), (3, 4, true)]);
+        assert!(!network.structurally_equal(&reversed));
+    }
+
+    #[test]
+    fn serde_round_trip_is_structurally_equal() {
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3], &[("highway", "primary"), ("name", "Ring")])]));
+        let json = serde_json::to_string(&network).unwrap();
+        let restored: RoadNetwork = serde_json::from_str(&json).unwrap();
+        assert!(restored.structurally_equal(&network));
+        assert_eq!(restored.shortest_path(1, 3), network.shortest_path(1, 3));
+    }
+
+    #[test]
+    fn gate_on_the_fastest_route_forces_a_detour() {
+        // the direct road 1-2-3 passes a gate at 2, the road over 4 doesn't
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49_f32, 7.002), (4, 49.001, 7.001)];
+        let ways: [fixtures::Way; 2] = [
+            (10, &[1, 2, 3], &[("highway", "residential")]),
+            (11, &[1, 4, 3], &[("highway", "residential")]),
+        ];
+        let xml = fixtures::osm_xml(&nodes, &ways).replace(
+            r#"<node id="2" lat="49" lon="7.001"/>"#,
+            "<node id=\"2\" lat=\"49\" lon=\"7.001\">\n  <tag k=\"barrier\" v=\"gate\"/>\n </node>",
+        );
+        let car = fixtures::parse(&xml);
+        assert_eq!(car.barrier(2), Some("gate"));
+        assert_eq!(car.shortest_path(1, 3).map(|(_, path)| path), Some(vec![1, 4, 3]));
+        let foot = fixtures::parse_into(RoadNetwork::new().with_speed_profile(SpeedProfile::foot()), &xml);
+        assert_eq!(foot.shortest_path(1, 3).map(|(_, path)| path), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn single_line_nodes_are_closed() {
+        let xml = fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3], &[("highway", "residential")])])
+            .replace(r#"<node id="1" lat="49" lon="7"/>"#, r#"<node id="1" lat="49" lon="7"></node>"#)
+            .replace(
+                r#"<node id="2" lat="49.001" lon="7.001"/>"#,
+                r#"<node id="2" lat="49.001" lon="7.001"><tag k="barrier" v="bollard"/></node>"#,
+            );
+        let network = fixtures::parse(&xml);
+        assert_eq!(network.barrier(2), Some("bollard"));
+        assert_eq!(network.barrier(1), None);
+        // the bollard blocks cars, the way after the nodes is still read on foot
+        assert_eq!(network.arc_count(), 0);
+        let foot = fixtures::parse_into(RoadNetwork::new().with_speed_profile(SpeedProfile::foot()), &xml);
+        assert_eq!(foot.shortest_path(1, 3).map(|(_, path)| path), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn nodes_in_bbox_query() {
+        let mut network = fixtures::grid(5);
+        // inclusive corners at nodes 7 and 19
+        let (min, max) = (Point {lat: 49.001, lon: 7.001}, Point {lat: 49.003, lon: 7.003});
+        assert_eq!(network.nodes_in_bbox(min, max), vec![7, 8, 9, 12, 13, 14, 17, 18, 19]);
+        assert_eq!(network.nodes_in_bbox(max, min), Vec::<isize>::new());
+        // nodes added after a query are found, those outside of the graph aren't
+        network.add_node(100, Point {lat: 49.002, lon: 7.0025});
+        assert_eq!(network.nodes_in_bbox(min, max).len(), 9);
+        network.add_arc(13, 100, 10_f32);
+        assert_eq!(network.nodes_in_bbox(min, max).len(), 10);
+        assert_eq!(network.nodes_in_bbox(Point {lat: 48_f32, lon: 6_f32}, Point {lat: 50_f32, lon: 8_f32}).len(), 26);
+    }
+
+    #[test]
+    fn nodes_in_bbox_across_the_antimeridian() {
+        let mut network = RoadNetwork::new();
+        network.add_nodes(vec![
+            (1, Point {lat: -17_f32, lon: 179.9}), (2, Point {lat: -17_f32, lon: -179.9}), (3, Point {lat: -17_f32, lon: 0_f32}),
+        ]);
+        network.add_arc(1, 2, 10_f32);
+        network.add_arc(2, 3, 10_f32);
+        let (min, max) = (Point {lat: -18_f32, lon: 179_f32}, Point {lat: -16_f32, lon: -179_f32});
+        assert_eq!(network.nodes_in_bbox(min, max), vec![1, 2]);
+    }
+
+    #[test]
+    fn missing_coord_policies() {
+        // node 4 lies outside of the extract
+        let xml = fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3, 4], &[("highway", "residential")])]);
+        let skip = fixtures::parse(&xml);
+        assert_eq!(skip.arc_count(), 4);
+        assert_eq!(skip.get_index(4), None);
+
+        let zero_cost = fixtures::parse_into(RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::ZeroCost), &xml);
+        assert_eq!(zero_cost.arc_count(), 6);
+        assert_eq!(fixtures::path_cost(&zero_cost, &[3, 4]), Some(0));
+        assert_eq!(zero_cost.shortest_path(1, 4).map(|(cost, _)| cost), zero_cost.shortest_path(1, 3).map(|(cost, _)| cost));
+
+        let mut error = RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::Error);
+        let error = error.read_from_reader(xml.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.into_inner().unwrap().downcast_ref::<OsmError>(), Some(&OsmError::MissingCoordinates(4)));
+    }
+
+    #[test]
+    fn collect_nodes_then_add_arcs() {
+        let collected: RoadNetwork = TRIANGLE.iter().map(|&(osm_id, lat, lon)| (osm_id, Point {lat, lon})).collect();
+        // nodes only enter the graph with their first arc
+        assert_eq!((collected.node_count(), collected.arc_count()), (0, 0));
+        assert_eq!(collected.nodes.get(&2).copied(), Some(Point {lat: 49.001, lon: 7.001}));
+        let mut network = collected.with_arcs([(1, 2, 10_f32), (2, 4, 10_f32)]);
+        // node 4 wasn't collected, so its arc is skipped
+        assert_eq!((network.node_count(), network.arc_count()), (2, 2));
+        network.extend([(4, Point {lat: 49.002, lon: 7.002})]);
+        network.add_arc(2, 4, 10_f32);
+        assert_eq!((network.node_count(), network.arc_count()), (3, 4));
+        assert_eq!(network.shortest_path(1, 4).map(|(_, path)| path), Some(vec![1, 2, 4]));
+    }
+
+    #[test]
+    fn duplicate_consecutive_refs_are_skipped() {
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 1, 2], residential)]));
+        assert_eq!(network.arc_count(), 2);
+        assert!(fixtures::has_arc(&network, 1, 2) && fixtures::has_arc(&network, 2, 1));
+        assert!(!fixtures::has_arc(&network, 1, 1));
+        assert_eq!(network.parse_stats().duplicate_refs, 1);
+        // a closed way repeats its first node at the end, which is no self-loop
+        let closed = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 2, 3, 1], residential)]));
+        assert_eq!(closed.arc_count(), 6);
+        assert_eq!(closed.parse_stats().duplicate_refs, 0);
+    }
+
+    #[test]
+    fn pathologically_long_arcs_are_clamped_or_rejected() {
+        let nodes = [(1, Point {lat: 0_f32, lon: 0_f32}), (2, Point {lat: 0_f32, lon: 1e15}), (3, Point {lat: 0_f32, lon: 1_f32})];
+        let mut clamped = RoadNetwork::new().with_distance_model(Euclidean);
+        clamped.add_nodes(nodes);
+        clamped.add_arcs([(1, 2, 0.001), (1, 3, 1_f32)]);
+        assert_eq!(fixtures::path_cost(&clamped, &[1, 2]), Some(MAX_ARC_COST));
+        assert_eq!(clamped.shortest_path(2, 3).map(|(cost, _)| cost), Some(MAX_ARC_COST + 1));
+        assert_eq!(clamped.parse_stats().rejected_long_arcs, 0);
+
+        let mut rejected = RoadNetwork::new().with_distance_model(Euclidean).with_max_arc_length(1000_f32);
+        rejected.add_nodes(nodes);
+        rejected.add_arcs([(1, 2, 0.001), (1, 3, 1_f32)]);
+        assert_eq!(rejected.arc_count(), 2);
+        assert_eq!(rejected.shortest_path(1, 2), None);
+        assert_eq!(rejected.parse_stats().rejected_long_arcs, 1);
+        // arcs of infinite length are always rejected
+        let mut infinite = RoadNetwork::new().with_distance_model(Euclidean);
+        infinite.add_nodes([(1, Point {lat: 0_f32, lon: -f32::MAX}), (2, Point {lat: 0_f32, lon: f32::MAX})]);
+        infinite.add_arc(1, 2, 1_f32);
+        assert_eq!((infinite.arc_count(), infinite.parse_stats().rejected_long_arcs), (0, 1));
+    }
+
+    #[test]
+    fn nearest_routable_node_skips_isolated_nodes() {
+        // the footway 3-4 gives no arcs for cars
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49.0001, 7.0015), (4, 49.001, 7.0015)];
+        let ways: [fixtures::Way; 2] = [(10, &[1, 2], &[("highway", "residential")]), (11, &[3, 4], &[("highway", "footway")])];
+        let mut network = fixtures::parse(&fixtures::osm_xml(&nodes, &ways));
+        let location = Point {lat: 49.0001, lon: 7.0014};
+        assert_eq!(network.nearest_node(location), Some(3));
+        assert_eq!(network.nearest_routable_node(location), Some(2));
+        assert_eq!(network.snap(location, SnapPolicy::default()), Some(2));
+        network.add_arc(2, 3, 10_f32);
+        assert_eq!(network.nearest_routable_node(location), Some(3));
+        assert_eq!(RoadNetwork::new().nearest_routable_node(location), None);
+    }
+
+    #[test]
+    fn ferries_get_arcs_at_the_ferry_speed() {
+        // about 7.3 km across the water
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.1)];
+        let ferry = |tags: &[(&str, &str)]| fixtures::parse(&fixtures::osm_xml(&nodes, &[(10, &[1, 2], tags)]));
+        let network = ferry(&[("route", "ferry")]);
+        let expected = arc_cost(network.distance(1, 2), 10_f32 * KMPH);
+        assert_eq!(fixtures::path_cost(&network, &[1, 2]), Some(expected));
+        assert_eq!(fixtures::path_cost(&network, &[2, 1]), Some(expected));
+        assert_eq!(network.arc_class(&network.adjacent_arcs()[0][0]), Some("ferry"));
+        // the duration wins over the speed (up to rounding down)
+        let scheduled = ferry(&[("route", "ferry"), ("duration", "00:30")]);
+        assert!(matches!(fixtures::path_cost(&scheduled, &[1, 2]), Some(1799..=1800)));
+        let oneway = ferry(&[("route", "ferry"), ("oneway", "yes")]);
+        assert_eq!(oneway.arc_count(), 1);
+        let no_ferries = SpeedProfile {ferry_speed_kmh: None, ..SpeedProfile::default()};
+        let xml = fixtures::osm_xml(&nodes, &[(10, &[1, 2], &[("route", "ferry")])]);
+        assert_eq!(fixtures::parse_into(RoadNetwork::new().with_speed_profile(no_ferries), &xml).arc_count(), 0);
+    }
+
+    #[test]
+    fn ferry_joins_two_road_islands() {
+        // roads 1-2 and 5-6 on either side, the ferry calls at 3 and 4
+        let nodes = [
+            (1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49.01, 7.02), (4, 49.02, 7.03), (5, 49.02, 7.05), (6, 49.02, 7.051),
+        ];
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        let roads = fixtures::osm_xml(&nodes, &[(10, &[1, 2], residential), (11, &[5, 6], residential)]);
+        assert_eq!(fixtures::parse(&roads).shortest_path(1, 6), None);
+        let xml = fixtures::osm_xml(&nodes, &[
+            (10, &[1, 2], residential), (11, &[5, 6], residential),
+            (20, &[2, 3, 4, 5], &[("route", "ferry"), ("duration", "20")]),
+        ]);
+        let network = fixtures::parse(&xml);
+        let (cost, path) = network.shortest_path(1, 6).unwrap();
+        assert_eq!(path, vec![1, 2, 3, 4, 5, 6]);
+        // the 20 minutes are split over three segments, each rounded down
+        let crossing = fixtures::path_cost(&network, &[2, 3, 4, 5]).unwrap();
+        assert!((1197..=1200).contains(&crossing), "{}", crossing);
+        assert_eq!(cost, fixtures::path_cost(&network, &[1, 2]).unwrap() + crossing + fixtures::path_cost(&network, &[5, 6]).unwrap());
+    }
+
+    #[test]
+    fn parse_ferry_durations() {
+        assert_eq!(parse_duration("45"), Some(2700));
+        assert_eq!(parse_duration("01:30"), Some(5400));
+        assert_eq!(parse_duration(" 0:01:05 "), Some(65));
+        assert_eq!(parse_duration("1:60"), None);
+        assert_eq!(parse_duration("1:00:00:00"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn with_capacity_starts_empty() {
+        let mut network = RoadNetwork::with_capacity(100, 350);
+        assert_eq!((network.node_count(), network.arc_count()), (0, 0));
+        assert!(network.nodes.capacity() >= 100 && network.osm_id_map.capacity() >= 100);
+        assert!(network.adjacent_arcs.capacity() >= 100 && network.points.capacity() >= 100);
+        network.add_nodes(TRIANGLE.iter().map(|&(osm_id, lat, lon)| (osm_id, Point {lat, lon})));
+        network.add_arc(1, 2, 10_f32);
+        // 3.5 arcs per node, rounded up
+        assert!(network.adjacent_arcs.iter().all(|arcs| arcs.capacity() >= 4));
+        let plain: RoadNetwork = TRIANGLE.iter().map(|&(osm_id, lat, lon)| (osm_id, Point {lat, lon})).collect();
+        assert!(network.structurally_equal(&plain.with_arcs([(1, 2, 10_f32)])));
+    }
+
+    #[test]
+    fn read_only_the_bbox() {
+        let mut network = RoadNetwork::new();
+        let (min, max) = (Point {lat: 47.99, lon: 7.84}, Point {lat: 48.001, lon: 7.852});
+        network.read_from_osm_file_in_bbox(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm"), min, max).unwrap();
+        // ways 100 and 101 are clipped to 1-2 and 2-4
+        assert_eq!(network.nodes_in_bbox(Point {lat: -90_f32, lon: -180_f32}, Point {lat: 90_f32, lon: 180_f32}), vec![1, 2, 4]);
+        assert_eq!(network.arc_count(), 4);
+        assert_eq!(network.way_ids_along(&[1, 2, 4]), vec![100, 101]);
+        assert_eq!((network.nodes.get(&3), network.nodes.get(&5)), (None, None));
+    }
+
+    #[test]
+    fn ways_reentering_the_bbox_are_split() {
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49.01, 7.002), (4, 49_f32, 7.003), (5, 49_f32, 7.004)];
+        let xml = fixtures::osm_xml(&nodes, &[(10, &[1, 2, 3, 4, 5], &[("highway", "residential")])]);
+        let mut network = RoadNetwork::new();
+        let bbox = (Point {lat: 48.9, lon: 6.9}, Point {lat: 49.001, lon: 7.1});
+        network._read_from_reader(xml.as_bytes(), Some(bbox), ParseProgress::default(), |_| {}, |_, _| Ok(())).unwrap();
+        assert_eq!(network.arc_count(), 4);
+        assert!(fixtures::has_arc(&network, 1, 2) && fixtures::has_arc(&network, 4, 5));
+        assert_eq!(network.shortest_path(1, 5), None);
+    }
+
+    #[test]
+    fn degrees_of_a_t_junction() {
+        // 1 - 2 - 3 with 4 branching off at 2
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true), (2, 4, true)]);
+        assert_eq!([1, 2, 3, 4].map(|osm_id| network.degree(osm_id)), [Some(1), Some(3), Some(1), Some(1)]);
+        assert!(network.is_intersection(2) && !network.is_intersection(1));
+        assert_eq!(network.degree(5), None);
+        assert!(!network.is_intersection(5));
+        // only outgoing arcs count
+        network.add_node(5, Point {lat: 49_f32, lon: 7.005});
+        network.add_oneway_arc(5, 3, 10_f32);
+        assert_eq!((network.degree(5), network.degree(3)), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn parse_points() {
+        assert_eq!("49.23,7.0".parse(), Ok(Point {lat: 49.23, lon: 7_f32}));
+        assert_eq!(" -33.9 , 151.2 ".parse(), Ok(Point {lat: -33.9, lon: 151.2}));
+        assert_eq!("90,-180".parse(), Ok(Point {lat: 90_f32, lon: -180_f32}));
+        // `Display` writes what `FromStr` reads
+        let point = Point {lat: 48.005, lon: 7.855};
+        assert_eq!(point.to_string().parse(), Ok(point));
+    }
+
+    #[test]
+    fn parse_invalid_points() {
+        assert_eq!("49.23 7.0".parse::<Point>(), Err(ParsePointError::MissingComma));
+        assert_eq!("49.23,east".parse::<Point>(), Err(ParsePointError::InvalidNumber("east".to_string())));
+        assert_eq!(",7".parse::<Point>(), Err(ParsePointError::InvalidNumber(String::new())));
+        assert_eq!("NaN,7".parse::<Point>(), Err(ParsePointError::InvalidNumber("NaN".to_string())));
+        assert_eq!("1,2,3".parse::<Point>(), Err(ParsePointError::InvalidNumber("2,3".to_string())));
+        assert_eq!("91,7".parse::<Point>(), Err(ParsePointError::OutOfRange));
+        assert_eq!("49,180.5".parse::<Point>(), Err(ParsePointError::OutOfRange));
+        assert_eq!(ParsePointError::MissingComma.to_string(), "expected \"lat,lon\"");
+    }
+
+    #[test]
+    fn arc_cost_at_half_the_speed_doubles() {
+        // about 730 m
+        let network = fixtures::network(&[(1, 11, true), (11, 12, false)]);
+        let at_50 = network.arc_cost_at_speed(1, 11, 50_f32).unwrap();
+        let at_25 = network.arc_cost_at_speed(1, 11, 25_f32).unwrap();
+        assert!((52..=53).contains(&at_50), "{}", at_50);
+        // both are rounded down
+        assert!(at_25 == 2 * at_50 || at_25 == 2 * at_50 + 1, "{} {}", at_25, at_50);
+        assert_eq!(network.arc_cost_at_speed(11, 1, 50_f32), Some(at_50));
+        assert_eq!(network.arc_cost_at_speed(12, 11, 50_f32), None);
+        assert_eq!(network.arc_cost_at_speed(1, 12, 50_f32), None);
+        assert_eq!(network.arc_cost_at_speed(1, 11, 0_f32), None);
+        assert_eq!(network.arc_cost_at_speed(1, 11, f32::NAN), None);
+    }
+
+    #[test]
+    fn decode_entities() {
+        assert_eq!(decode_xml_entities("Straße &amp; Gasse"), "Straße & Gasse");
+        assert_eq!(decode_xml_entities("&lt;&gt;&quot;&apos;&#223;&#xDF;"), "<>\"'ßß");
+        // unknown entities and stray ampersands are kept
+        assert_eq!(decode_xml_entities("&nbsp; & &#xZZ;"), "&nbsp; & &#xZZ;");
+        assert!(matches!(decode_xml_entities("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn display_points_and_arcs() {
+        let point = Point {lat: 49.234, lon: 7.012};
+        assert_eq!(point.to_string(), "49.234, 7.012");
+        assert_eq!(format!("{:.1}", Point {lat: 49.25, lon: -7_f32}), "49.2, -7.0");
+        let network = fixtures::network(&[(1, 2, false)]);
+        let arc = &network.adjacent_arcs()[0][0];
+        assert_eq!(arc.to_string(), format!("-> node[1] {}s", arc.cost));
+    }
+
+    #[test]
+    fn source_metadata_and_comments() {
+        let xml = fixtures::osm_xml(&[(1, 49_f32, 7_f32), (2, 49_f32, 7.001)], &[(10, &[1, 2], &[("highway", "residential")])]);
+        let xml = xml.replace("generator=\"fixture\">", concat!(
+            "generator=\"osmium/1.16 &amp; friends\"> <!-- exported\n",
+            " <node id=\"3\" lat=\"49\" lon=\"7.002\"/>\n",
+            " <way id=\"11\">\n  <nd ref=\"2\"/>\n  <nd ref=\"3\"/>\n",
+            "  <tag k=\"highway\" v=\"primary\"/>\n </way>\n",
+            "-->\n <!-- one line --> <node id=\"4\" lat=\"49\" lon=\"7.003\"/>",
+        ));
+        let network = fixtures::parse(&xml);
+        let metadata = network.source_metadata().unwrap();
+        assert_eq!(metadata.version.as_deref(), Some("0.6"));
+        assert_eq!(metadata.generator.as_deref(), Some("osmium/1.16 & friends"));
+        // the commented-out node and way are ignored, the node after the
+        // comment isn't
+        assert_eq!(network.nodes.get(&3), None);
+        assert!(network.nodes.contains_key(&4));
+        assert_eq!(network.arc_count(), 2);
+        assert_eq!(RoadNetwork::new().source_metadata(), None);
+    }
+
+    #[test]
+    fn invalid_utf8_only_spoils_the_value() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49_f32, 7.002)],
+            &[(10, &[1, 2], &[("highway", "residential"), ("name", "Stra#e")]), (11, &[2, 3], &[("highway", "primary")])],
+        );
+        // a Latin-1 "ß" (the `#`) in the name and a stray byte in a node line
+        let xml = xml.replace("id=\"2\"", "user=\"#\" id=\"2\"");
+        let bytes: Vec<u8> = xml.bytes().map(|byte| if byte == b'#' { 0xdf } else { byte }).collect();
+        let mut network = RoadNetwork::new();
+        network.read_from_reader(&bytes[..]).unwrap();
+        assert_eq!(network.parse_stats().invalid_utf8_lines, 2);
+        assert_eq!((network.node_count(), network.arc_count()), (3, 4));
+        let arc = network.adjacent_arcs()[network.get_index(1).unwrap()][0];
+        assert_eq!(network.arc_name(&arc), Some("Stra\u{fffd}e"));
+        assert_eq!(fixtures::parse(&xml.replace('#', "\u{df}")).parse_stats().invalid_utf8_lines, 0);
+    }
+
+    #[test]
+    fn self_loops_are_refused_and_zero_length_arcs_cost_one() {
+        let mut network = RoadNetwork::new();
+        network.add_nodes([(1, Point {lat: 49_f32, lon: 7_f32}), (2, Point {lat: 49_f32, lon: 7_f32}), (3, Point {lat: 49_f32, lon: 7.001})]);
+        network.add_arc(1, 1, 10_f32);
+        assert_eq!((network.arc_count(), network.parse_stats().self_loops), (0, 1));
+        // 2 lies on top of 1, so 1-2 is no shortcut to 3
+        network.add_arcs([(1, 2, 10_f32), (2, 3, 10_f32)]);
+        assert_eq!(network.parse_stats().zero_length_arcs, 1);
+        assert_eq!((fixtures::path_cost(&network, &[1, 2]), fixtures::path_cost(&network, &[2, 1])), (Some(1), Some(1)));
+        let (cost, path) = network.shortest_path(1, 3).unwrap();
+        assert_eq!((cost, path), (1 + fixtures::path_cost(&network, &[2, 3]).unwrap(), vec![1, 2, 3]));
+        assert_eq!(network.arc_cost_at_speed(1, 2, 50_f32), Some(1));
+        assert_eq!(network.shortest_path(1, 1), Some((0, vec![1])));
+
+        // a doubled ref and the closing ref of a ring: one duplicate, no loops
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        let network = fixtures::parse(&fixtures::osm_xml(&TRIANGLE, &[(10, &[1, 1, 2], residential), (11, &[2, 3, 1, 2], residential)]));
+        let stats = network.parse_stats();
+        assert_eq!((stats.duplicate_refs, stats.self_loops, stats.zero_length_arcs), (1, 0, 0));
+        assert!(network.adjacent_arcs().iter().enumerate().all(|(index, arcs)| arcs.iter().all(|arc| arc.index != index && arc.cost > 0)));
+    }
+
+    #[test]
+    fn arc_cost_at_speed_uses_the_stored_length() {
+        let mut network = RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::ZeroCost);
+        network.add_nodes([(1, Point {lat: 49_f32, lon: 7_f32}), (2, Point {lat: 49_f32, lon: 7.01})]);
+        network.add_arcs([(1, 2, 10_f32), (2, 3, 10_f32)]);
+        let arc = network.adjacent_arcs()[0][0];
+        assert_eq!(network.arc_cost_at_speed(1, 2, 36_f32), Some(arc.cost));
+        assert_eq!(network.arc_cost_at_speed(1, 2, 36_f32), Some(arc_cost(arc.length.unwrap(), 10_f32)));
+        // the node is moved after the arc was built
+        network.add_node(2, Point {lat: 49_f32, lon: 7.02});
+        assert_eq!(network.arc_cost_at_speed(1, 2, 36_f32), Some(arc.cost));
+        // arcs to nodes without coordinates have no length
+        assert_eq!(fixtures::path_cost(&network, &[2, 3]), Some(0));
+        assert_eq!(network.arc_cost_at_speed(2, 3, 36_f32), None);
+    }
+
+    #[test]
+    fn tags_are_collected_generically() {
+        assert_eq!(
+            parse_tag_line(r#"  <tag k="maxspeed:conditional" v="30 @ (22:00-06:00)"/>"#),
+            Some(("maxspeed:conditional".to_string(), "30 @ (22:00-06:00)".to_string()))
+        );
+        assert_eq!(parse_tag_line(r#"<tag k="note" v=""/>"#), Some(("note".to_string(), String::new())));
+        assert_eq!(parse_tag_line(r#"<tag k="a&amp;b" v="&quot;x&quot;"/>"#), Some(("a&b".to_string(), "\"x\"".to_string())));
+        assert_eq!(parse_tag_line(r#"<nd ref="1"/>"#), None);
+        // of duplicate keys the last one counts, unknown keys are dropped
+        let element = concat!(
+            "<way id=\"10\">\n <nd ref=\"1\"/>\n <nd ref=\"2\"/>\n",
+            " <tag k=\"highway\" v=\"track\"/>\n <tag k=\"highway\" v=\"residential\"/>\n",
+            " <tag k=\"name\" v=\"&quot;Alte&quot; Stra&#xDF;e\"/>\n <tag k=\"surface\" v=\"asphalt\"/>\n</way>",
+        );
+        let (_, tags) = parse_way(element).unwrap();
+        assert_eq!(tags.highway.as_deref(), Some("residential"));
+        assert_eq!(tags.name.as_deref(), Some("\"Alte\" Straße"));
+        let mut expected = WayTags::default();
+        expected.set("highway", "residential");
+        expected.set("name", "\"Alte\" Straße");
+        assert_eq!(tags, expected);
+        let tags = HashMap::from([("oneway".to_string(), "-1".to_string()), ("lanes".to_string(), "2".to_string())]);
+        assert_eq!(WayTags::from_tags(&tags).oneway.as_deref(), Some("-1"));
+        assert_eq!(WayTags::from_tags(&tags).lanes.as_deref(), Some("2"));
+
+        // node tags: only the barrier matters, other tags don't leak into ways
+        let xml = fixtures::osm_xml(&[(1, 49_f32, 7_f32), (3, 49_f32, 7.002)], &[(10, &[1, 2, 3], &[("highway", "residential")])]);
+        let xml = xml.replace(" <way", concat!(
+            " <node id=\"2\" lat=\"49\" lon=\"7.001\">\n  <tag k=\"highway\" v=\"motorway\"/>\n",
+            "  <tag k=\"barrier\" v=\"gate\"/>\n  <tag k=\"barrier\" v=\"bollard\"/>\n </node>\n <way",
+        ));
+        let network = fixtures::parse(&xml);
+        assert_eq!(network.barrier(2), Some("bollard"));
+        assert_eq!(network.barrier(1), None);
+        assert_eq!(network.arc_count(), 0);
+    }
+
+    #[test]
+    fn highway_filter_drops_classes_while_parsing() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49.001, 7.001), (4, 49.002, 7.001), (5, 49.002, 7.002)],
+            &[
+                (10, &[1, 2], &[("highway", "primary")]),
+                (11, &[2, 3, 4], &[("highway", "residential")]),
+                (12, &[4, 5], &[("route", "ferry")]),
+                (13, &[1, 5], &[("building", "yes")]),
+            ],
+        );
+        let only = fixtures::parse_into(RoadNetwork::new().with_highway_filter(HighwayFilter::only(&["primary", "trunk"])), &xml);
+        assert!(fixtures::has_arc(&only, 1, 2) && fixtures::has_arc(&only, 2, 1));
+        assert!(!fixtures::has_arc(&only, 2, 3) && !fixtures::has_arc(&only, 4, 5));
+        assert_eq!((only.node_count(), only.arc_count()), (2, 2));
+        assert_eq!(only.get_index(3), None);
+        // ways that aren't roads at all don't count as filtered
+        assert_eq!(only.parse_stats().filtered_ways, 2);
+
+        let excluded = fixtures::parse_into(RoadNetwork::new().with_highway_filter(HighwayFilter::exclude(&["ferry"])), &xml);
+        assert!(fixtures::has_arc(&excluded, 2, 3) && !fixtures::has_arc(&excluded, 4, 5));
+        assert_eq!(excluded.parse_stats().filtered_ways, 1);
+        let all = fixtures::parse(&xml);
+        assert!(fixtures::has_arc(&all, 4, 5));
+        assert_eq!(all.parse_stats().filtered_ways, 0);
+        assert!(HighwayFilter::All.allows("anything") && !HighwayFilter::only(&[]).allows("primary"));
+    }
+
+    #[test]
+    fn merge_fills_in_missing_points() {
+        // node 3 is in the graph without coordinates until the merge
+        let mut network = RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::ZeroCost);
+        network.add_nodes([(1, Point {lat: 49_f32, lon: 7.001}), (2, Point {lat: 49_f32, lon: 7.002})]);
+        network.add_arcs([(1, 2, 10_f32), (2, 3, 10_f32)]);
+        let index = network.get_index(3).unwrap();
+        assert_eq!(network.point_by_index(index), None);
+        assert_eq!(network.nearest_node(Point {lat: 49_f32, lon: 7.003}), Some(2));
+        let mut other = fixtures::network(&[(3, 4, true)]);
+        // a different location for node 2 doesn't replace the existing one
+        other.add_node(2, Point {lat: 50_f32, lon: 8_f32});
+        network.merge(other);
+        assert_eq!(network.get_index(3), Some(index));
+        assert_eq!(network.point_by_index(index), Some(Point {lat: 49_f32, lon: 7.003}));
+        assert_eq!(network.point_by_osm_id(2), Some(Point {lat: 49_f32, lon: 7.002}));
+        assert_eq!(network.point_by_index(network.get_index(4).unwrap()), Some(Point {lat: 49_f32, lon: 7.004}));
+        assert!((0..network.node_count()).all(|index| network.point_by_index(index) == network.point_by_osm_id(network.osm_ids[index])));
+        // the spatial index sees the merged nodes
+        assert_eq!(network.nearest_node(Point {lat: 49_f32, lon: 7.0031}), Some(3));
+    }
+
+    #[test]
+    fn late_node_definitions_fill_their_slot() {
+        // node 3 only appears after the way using it
+        let xml = fixtures::osm_xml(&[(1, 49_f32, 7_f32), (2, 49_f32, 7.001)], &[(10, &[1, 2, 3], &[("highway", "residential")])])
+            .replace("</osm>", " <node id=\"3\" lat=\"49\" lon=\"7.002\"/>\n</osm>");
+        let network = fixtures::parse_into(RoadNetwork::new().with_missing_coord_policy(MissingCoordPolicy::ZeroCost), &xml);
+        let index = network.get_index(3).unwrap();
+        assert_eq!(network.point_by_index(index), Some(Point {lat: 49_f32, lon: 7.002}));
+        assert_eq!(network.point_by_osm_id(3), network.point_by_index(index));
+        // the arc was built while 3 had no coordinates
+        assert_eq!(fixtures::path_cost(&network, &[2, 3]), Some(0));
+        let skipped = fixtures::parse(&xml);
+        assert_eq!((skipped.get_index(3), skipped.point_by_osm_id(3)), (None, Some(Point {lat: 49_f32, lon: 7.002})));
+        assert_eq!(skipped.arc_count(), 2);
+    }
+
+    #[test]
+    fn recompute_costs_switches_from_car_to_foot() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49.002, 7.005), (4, 48.998, 7.005)],
+            &[
+                (10, &[1, 2], &[("highway", "motorway")]),
+                (11, &[1, 3, 2], &[("highway", "footway"), ("maxspeed:conditional", "30 @ (22:00-06:00)")]),
+                (12, &[1, 4, 2], &[("highway", "residential"), ("maxspeed", "50"), ("maxspeed:conditional", "30 @ (22:00-06:00)")]),
+            ],
+        );
+        let car = SpeedProfile {default_unknown_speed: Some(20_f32), ..SpeedProfile::car()};
+        let mut network = fixtures::parse_into(RoadNetwork::new().with_speed_profile(car), &xml);
+        assert_eq!(network.shortest_path(1, 2).unwrap().1, vec![1, 2]);
+        let night = 23 * 3600;
+        assert_ne!(network.shortest_path_departing_at(1, 4, night), network.shortest_path(1, 4));
+
+        network.recompute_costs(&SpeedProfile::foot());
+        assert!(!fixtures::has_arc(&network, 1, 2) && !fixtures::has_arc(&network, 2, 1));
+        for (from, to) in [(1, 3), (3, 2), (1, 4), (4, 2)] {
+            let length = network.distance(from, to);
+            let walking = (length / (5_f32 * KMPH)) as usize;
+            assert_eq!(fixtures::path_cost(&network, &[from, to]), Some(walking));
+            assert_eq!(fixtures::path_cost(&network, &[to, from]), Some(walking));
+        }
+        // the conditions were for cars, walking takes as long at night
+        for (from, to) in [(1, 2), (1, 3), (1, 4)] {
+            let route = network.shortest_path(from, to);
+            assert!(route.is_some());
+            assert_eq!(network.shortest_path_departing_at(from, to, night), route);
+        }
+    }
+}
@@ -1,176 +1,256 @@
+extern crate hello_rust;
 use std::fs::File;
-use std::io::BufReader;
-use std::collections::HashMap;
-use std::ops::Sub;
-use std::io::prelude::*;
-
-#[macro_use] extern crate lazy_static;
-extern crate regex;
-use regex::Regex;
-
-lazy_static! {
-    static ref OSM_NODE_RE: Regex = (
-        Regex::new(r#"id="(\d+)" lat="([0-9.]+)" lon="([0-9.]+)""#).unwrap()
-    );
-    static ref OSM_HIGHWAY_RE: Regex = Regex::new(r#"k="highway" v="([a-z_]+)""#).unwrap();
-    static ref OSM_ND_RE: Regex = Regex::new(r#"<nd ref="(\d+)""#).unwrap();
-}
+use std::io::{self, BufRead, BufWriter, Write};
 
-const KMPH: f32 = 1000_f32 / 3600_f32;  // km/h to m/s factor
+use clap::{Parser, Subcommand, ValueEnum};
 
+use hello_rust::{HighwayFilter, Point, RoadNetwork, SnapPolicy, SpeedProfile};
 
-#[derive(Debug, Copy, Clone)]
-struct Arc {
-    index: usize,
-    cost: usize,  // in seconds
-}
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-struct Point {
-    lat: f32,
-    lon: f32,
+#[derive(Parser)]
+#[command(about = "Road networks from OpenStreetMap data")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Cap every road's speed at this many km/h (ignored for .cache files)
+    #[arg(long, global = true, value_parser = parse_speed)]
+    max_speed_override: Option<f32>,
+    /// Speed of one highway class as CLASS=KMH, e.g. service=20 (repeatable, ignored for .cache files)
+    #[arg(long = "speed", global = true, value_parser = parse_class_speed)]
+    class_speeds: Vec<(String, f32)>,
+    /// Only turn these highway classes into roads, e.g. motorway,trunk,primary (ignored for .cache files)
+    #[arg(long, global = true, value_delimiter = ',')]
+    only_highways: Vec<String>,
+    /// Worker threads for parallel steps, 0 for one per core
+    #[cfg(feature = "rayon")]
+    #[arg(long, global = true, default_value_t = 0)]
+    threads: usize,
 }
 
-#[derive(Debug)]
-struct RoadNetwork {
-    osm_id_map: HashMap<isize, usize>,
-    nodes: HashMap<isize, Point>,
-    adjacent_arcs: Vec<Vec<Arc>>,
+#[derive(Subcommand)]
+enum Command {
+    /// Print summary statistics
+    Stats {
+        file: String,
+        /// Write one line per node with its neighbors and costs
+        #[arg(long)]
+        dump: Option<String>,
+        #[arg(long, value_enum, default_value = "text")]
+        format: StatsFormat,
+    },
+    /// Print the fastest route between two OSM node IDs or "lat,lon" coordinates
+    Route {
+        file: String,
+        #[arg(long, alias = "from-coord", value_parser = parse_location, allow_hyphen_values = true)]
+        from: Location,
+        #[arg(long, alias = "to-coord", value_parser = parse_location, allow_hyphen_values = true)]
+        to: Location,
+    },
+    /// Export the road graph
+    Export {
+        file: String,
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        #[arg(long)]
+        out: String,
+    },
+    /// Load the network once, then answer queries read from stdin
+    #[command(long_flag = "repl")]
+    Repl {
+        file: String,
+    },
+    /// Save the parsed network for faster loading
+    Convert {
+        file: String,
+        #[arg(long)]
+        to_cache: String,
+    },
 }
 
-impl Sub for Point {
-    type Output = f32;
+#[derive(Copy, Clone, ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
+}
 
-    fn sub(self, other: Point) -> f32 {
-        (
-            ((self.lat - other.lat) * 111_229_f32).powi(2) +
-            ((self.lon - other.lon) * 71_695_f32).powi(2)
-        ).sqrt()
-    }
+#[derive(Copy, Clone, ValueEnum)]
+enum ExportFormat {
+    Geojson,
+    Csv,
+    Dimacs,
+    Graphml,
+    Wkt,
+    Osm,
 }
 
-impl RoadNetwork {
-    pub fn new() -> RoadNetwork {
-        RoadNetwork {
-            osm_id_map: HashMap::new(),
-            nodes: HashMap::new(),
-            adjacent_arcs: Vec::new(),
-        }
-    }
+// Where a route starts or ends; coordinates get snapped to the closest node,
+// or to a nearby one connected to the other end if both are coordinates.
+#[derive(Copy, Clone, Debug)]
+enum Location {
+    Node(isize),
+    Coordinates(Point),
+}
 
-    pub fn add_node(&mut self, osm_id: isize, location: Point) {
-        self.nodes.insert(osm_id, location);
+fn parse_location(value: &str) -> Result<Location, String> {
+    if let Ok(osm_id) = value.parse::<isize>() {
+        return Ok(Location::Node(osm_id));
     }
+    value.parse::<Point>()
+        .map(Location::Coordinates)
+        .map_err(|error| format!("`{}` is neither a node ID nor coordinates: {}", value, error))
+}
 
-    pub fn get_index(&self, osm_id: isize) -> Option<usize> {
-        match self.osm_id_map.get(&osm_id) {
-            Some(osm_id) => Some(*osm_id),
-            None => None,
-        }
-    }
+fn parse_speed(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok()
+        .filter(|speed| speed.is_finite() && *speed > 0_f32)
+        .ok_or_else(|| format!("`{}` is not a positive speed", value))
+}
 
-    pub fn get_or_create_index(&mut self, osm_id: isize) -> usize {
-        match self.get_index(osm_id) {
-            Some(index) => index,
-            None => {
-                let index = self.adjacent_arcs.len();
-                self.adjacent_arcs.push(Vec::new());
-                self.osm_id_map.insert(osm_id, index);
-                index
-            }
-        }
-    }
+fn parse_class_speed(value: &str) -> Result<(String, f32), String> {
+    let (class, speed) = value.split_once('=')
+        .ok_or_else(|| format!("expected CLASS=KMH, got `{}`", value))?;
+    Ok((class.trim().to_string(), parse_speed(speed.trim())?))
+}
 
-    pub fn distance(&self, osm_id_a: isize, osm_id_b: isize) -> f32 {
-        let location_a = *self.nodes.get(&osm_id_a).unwrap();
-        let location_b = *self.nodes.get(&osm_id_b).unwrap();
-        location_a - location_b
+// Picks the reader by extension: `.cache` (see `convert`), `.json` (Overpass)
+// or OSM XML. Cached networks keep the profile and filter they were built with.
+fn load(filename: &str, profile: &SpeedProfile, filter: &HighwayFilter) -> io::Result<RoadNetwork> {
+    if filename.ends_with(".cache") {
+        return RoadNetwork::read_cache_file(filename);
     }
-
-    fn _push_arc_at_index(&mut self, index: usize, arc: Arc) {
-        let node = self.adjacent_arcs.get_mut(index).unwrap();
-        node.push(arc);
+    let mut road_network = RoadNetwork::new()
+        .with_speed_profile(profile.clone())
+        .with_highway_filter(filter.clone());
+    if filename.ends_with(".json") {
+        road_network.read_from_osm_json(io::BufReader::new(File::open(filename)?))?;
+    } else {
+        road_network.read_from_osm_file(filename)?;
     }
+    Ok(road_network)
+}
 
-    pub fn add_arc(&mut self, osm_id_a: isize, osm_id_b: isize, speed_factor: f32) {
-        let cost = (self.distance(osm_id_a, osm_id_b) / speed_factor) as usize;
-        let index_a = self.get_or_create_index(osm_id_a);
-        let index_b = self.get_or_create_index(osm_id_b);
-        self._push_arc_at_index(index_a, Arc {index: index_b, cost});
-        self._push_arc_at_index(index_b, Arc {index: index_a, cost});
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    #[cfg(feature = "rayon")]
+    hello_rust::set_thread_count(cli.threads).map_err(io::Error::other)?;
+    let mut profile = SpeedProfile {global_speed_cap_kmh: cli.max_speed_override, ..SpeedProfile::car()};
+    for (class, speed) in cli.class_speeds {
+        profile = profile.with_speed_kmh(&class, speed).map_err(io::Error::other)?;
     }
-
-    pub fn read_from_osm_file(&mut self, filename: &str) -> std::io::Result<()>{
-        let file = File::open(filename)?;
-        let reader = BufReader::new(file);
-        let mut hops: Vec<isize> = Vec::new();
-        let mut is_way = false;
-        let mut is_highway = false;
-        let mut speed_factor = 0_f32;
-
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                let trimmed_line = line.trim_start();
-                if let Some(cap) = OSM_NODE_RE.captures(trimmed_line) {
-                    self.add_node(
-                        cap[1].parse::<isize>().unwrap(),
-                        Point{
-                            lat: cap[2].parse::<f32>().unwrap(),
-                            lon: cap[3].parse::<f32>().unwrap()
-                        }
-                    );
-                } else if trimmed_line.starts_with(r"<way ") {
-                    hops = Vec::new();
-                    is_way = true;
-                    is_highway = false;
-                } else if is_way {
-                    if let Some(cap) = OSM_ND_RE.captures(trimmed_line) {
-                        hops.push(cap[1].parse::<isize>().unwrap());
-                    } else if let Some(cap) = OSM_HIGHWAY_RE.captures(trimmed_line) {
-                        is_highway = true;
-                        speed_factor = KMPH * match &cap[1] {
-                            "motorway" => 110_f32,
-                            "trunk" => 110_f32,
-                            "primary" => 70_f32,
-                            "secondary" => 60_f32,
-                            "tertiary" => 50_f32,
-                            "motorway_link" => 50_f32,
-                            "trunk_link" => 50_f32,
-                            "primary_link" => 50_f32,
-                            "secondary_link" => 50_f32,
-                            "road" => 40_f32,
-                            "unclassified" => 40_f32,
-                            "residential" => 30_f32,
-                            "unsurfaced" => 30_f32,
-                            "living_street" => 10_f32,
-                            "service" => 5_f32,
-                            &_ => {
-                                is_highway = false;
-                                0_f32
-                            }
-                        };
-                    } else if trimmed_line.starts_with(r"</way") {
-                        if is_highway && speed_factor > 0_f32{
-                            let mut previous = 0;
-                            for hop in hops.clone() {
-                                if previous > 0 {
-                                    self.add_arc(hop, previous, speed_factor);
-                                }
-                                previous = hop;
-                            }
-                        }
-                        is_way = false;
+    let filter = if cli.only_highways.is_empty() {
+        HighwayFilter::All
+    } else {
+        HighwayFilter::Only(cli.only_highways)
+    };
+    match cli.command {
+        Command::Stats {file, dump, format} => {
+            let road_network = load(&file, &profile, &filter)?;
+            match format {
+                StatsFormat::Text => {
+                    println!("nodes: {}", road_network.node_count());
+                    println!("arcs: {}", road_network.arc_count());
+                    println!("{:?}", road_network.arc_cost_summary());
+                    println!("{:?}", road_network.parse_stats());
+                }
+                StatsFormat::Json => println!("{}", road_network.stats_json()?),
+            }
+            if let Some(path) = dump {
+                let mut writer = BufWriter::new(File::create(path)?);
+                road_network.write_adjacency(&mut writer)?;
+                writer.flush()?;
+            }
+        }
+        Command::Route {file, from: Location::Coordinates(from), to: Location::Coordinates(to)} => {
+            let road_network = load(&file, &profile, &filter)?;
+            match road_network.route_between_points(from, to) {
+                Some(route) => {
+                    println!("cost: {}s", route.cost);
+                    println!("path: {:?}", route.path);
+                    println!("ways: {:?}", road_network.way_ids_along(&route.path));
+                    println!("snapped: {:.0}m, {:.0}m", route.snap_distances.0, route.snap_distances.1);
+                }
+                None => {
+                    eprintln!("no route from {} to {}", from, to);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Route {file, from, to} => {
+            let road_network = load(&file, &profile, &filter)?;
+            let resolve = |location| match location {
+                Location::Node(osm_id) => osm_id,
+                Location::Coordinates(point) => road_network.snap(point, SnapPolicy::default())
+                    .unwrap_or_else(|| {
+                        eprintln!("no road near {}", point);
+                        std::process::exit(1);
+                    }),
+            };
+            let (from, to) = (resolve(from), resolve(to));
+            match road_network.shortest_path(from, to) {
+                Some((cost, path)) => {
+                    println!("cost: {}s", cost);
+                    println!("path: {:?}", path);
+                    println!("ways: {:?}", road_network.way_ids_along(&path));
+                }
+                None => {
+                    eprintln!("no route from {} to {}", from, to);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Export {file, format, out} => {
+            let road_network = load(&file, &profile, &filter)?;
+            let mut writer = BufWriter::new(File::create(out)?);
+            match format {
+                ExportFormat::Geojson => road_network.write_geojson(&mut writer)?,
+                ExportFormat::Csv => road_network.write_csv(&mut writer)?,
+                ExportFormat::Dimacs => road_network.write_dimacs(&mut writer)?,
+                ExportFormat::Graphml => road_network.write_graphml(&mut writer)?,
+                ExportFormat::Osm => road_network.write_osm_xml(&mut writer)?,
+                ExportFormat::Wkt => {
+                    for line in road_network.to_wkt_lines() {
+                        writeln!(writer, "{}", line)?;
                     }
                 }
             }
+            writer.flush()?;
+        }
+        Command::Repl {file} => {
+            let road_network = load(&file, &profile, &filter)?;
+            println!("{}", hello_rust::REPL_USAGE);
+            let mut stdout = io::stdout();
+            write!(stdout, "> ")?;
+            stdout.flush()?;
+            for line in io::stdin().lock().lines() {
+                let line = line?;
+                if matches!(line.trim(), "quit" | "exit") {
+                    break;
+                }
+                if !line.trim().is_empty() {
+                    println!("{}", road_network.repl_command(&line));
+                }
+                write!(stdout, "> ")?;
+                stdout.flush()?;
+            }
+        }
+        Command::Convert {file, to_cache} => {
+            load(&file, &profile, &filter)?.write_cache_file(&to_cache)?;
         }
-        Ok(())
     }
+    Ok(())
 }
 
-fn main() -> std::io::Result<()> {
-    let mut road_network = RoadNetwork::new();
-    road_network.read_from_osm_file("saarland.osm")?;
-    println!("{:?}", road_network.adjacent_arcs);
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
 }
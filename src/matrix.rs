@@ -0,0 +1,97 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::RoadNetwork;
+
+// Size of rayon's global thread pool used by the parallel methods, 0 for
+// one thread per core. Must be called before anything runs on the pool;
+// afterwards (or on a second call) the pool can't change and this fails.
+#[cfg(feature = "rayon")]
+pub fn set_thread_count(threads: usize) -> Result<(), rayon::ThreadPoolBuildError> {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+}
+
+impl RoadNetwork {
+    // Travel times from every source to every target (OSM IDs), one row per
+    // source in the order of `sources`. `None` where the target can't be
+    // reached or either node is unknown. Runs one Dijkstra per source.
+    pub fn distance_matrix(&self, sources: &[isize], targets: &[isize]) -> Vec<Vec<Option<usize>>> {
+        sources.iter().map(|&source| self._matrix_row(source, targets)).collect()
+    }
+
+    // Same result as `distance_matrix`, with the searches spread over rayon's
+    // global thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn distance_matrix_parallel(&self, sources: &[isize], targets: &[isize]) -> Vec<Vec<Option<usize>>> {
+        sources.par_iter().map(|&source| self._matrix_row(source, targets)).collect()
+    }
+
+    fn _matrix_row(&self, source: isize, targets: &[isize]) -> Vec<Option<usize>> {
+        match self.shortest_path_tree(source, None) {
+            Some(tree) => targets.iter().map(|&target| tree.cost_to(target)).collect(),
+            None => vec![None; targets.len()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "rayon")]
+    use rayon::prelude::*;
+
+    use crate::fixtures;
+
+    #[test]
+    fn matrix_rows_follow_the_sources() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, false)]);
+        let matrix = network.distance_matrix(&[3, 1, 4], &[1, 3]);
+        let cost = |from, to| network.shortest_path(from, to).map(|(cost, _)| cost);
+        assert_eq!(matrix, vec![vec![None, Some(0)], vec![Some(0), cost(1, 3)], vec![None, None]]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matrix_matches_sequential() {
+        let network = fixtures::grid(8);
+        let nodes: Vec<isize> = (1..=64).chain([0, 100]).collect();
+        let sequential = network.distance_matrix(&nodes, &nodes[..20]);
+        assert_eq!(network.distance_matrix_parallel(&nodes, &nodes[..20]), sequential);
+        assert_eq!(sequential.len(), 66);
+    }
+
+    // rayon's global pool can only be set up once per process and other
+    // tests may already use it, so the check runs in a fresh copy of the
+    // test binary running only this test.
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn pool_size_follows_set_thread_count() {
+        use std::collections::HashSet;
+        use std::sync::Mutex;
+
+        const CHILD: &str = "HELLO_RUST_THREAD_POOL_CHILD";
+        if std::env::var_os(CHILD).is_none() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["matrix::tests::pool_size_follows_set_thread_count", "--exact", "--test-threads=1"])
+                .env(CHILD, "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+        crate::set_thread_count(3).unwrap();
+        assert!(crate::set_thread_count(2).is_err());
+        assert_eq!(rayon::current_num_threads(), 3);
+        let workers = Mutex::new(HashSet::new());
+        let network = fixtures::grid(8);
+        let nodes: Vec<isize> = (1..=64).collect();
+        let matrix: Vec<Vec<Option<usize>>> = nodes.par_iter()
+            .map(|&source| {
+                workers.lock().unwrap().insert(std::thread::current().id());
+                network.distance_matrix_parallel(&[source], &nodes).remove(0)
+            })
+            .collect();
+        assert_eq!(matrix, network.distance_matrix(&nodes, &nodes));
+        let workers = workers.into_inner().unwrap().len();
+        assert!((1..=3).contains(&workers), "{}", workers);
+    }
+}
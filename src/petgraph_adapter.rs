@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use petgraph::graph::{Graph, NodeIndex};
+
+use crate::{Point, RoadNetwork};
+
+impl RoadNetwork {
+    // Directed petgraph graph with one node per graph node (weighted with its
+    // coordinates) and one edge per arc (weighted with its cost), plus the
+    // node index of each OSM ID. Nodes without coordinates and their arcs are
+    // left out.
+    pub fn to_petgraph(&self) -> (Graph<Point, usize>, HashMap<isize, NodeIndex>) {
+        let mut graph = Graph::with_capacity(self.node_count(), self.arc_count());
+        let mut node_indices = HashMap::with_capacity(self.node_count());
+        for osm_id in &self.osm_ids {
+            if let Some(&location) = self.nodes.get(osm_id) {
+                node_indices.insert(*osm_id, graph.add_node(location));
+            }
+        }
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            let Some(&tail) = node_indices.get(&self.osm_ids[index]) else { continue };
+            for arc in arcs {
+                if let Some(&head) = node_indices.get(&self.osm_ids[arc.index]) {
+                    graph.add_edge(tail, head, arc.cost);
+                }
+            }
+        }
+        (graph, node_indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::algo::dijkstra;
+
+    use crate::fixtures;
+
+    #[test]
+    fn petgraph_dijkstra_agrees() {
+        let mut network = fixtures::grid(4);
+        network.add_oneway_arc(1, 16, 1_f32);
+        let (graph, node_indices) = network.to_petgraph();
+        assert_eq!((graph.node_count(), graph.edge_count()), (network.node_count(), network.arc_count()));
+        assert_eq!(graph[node_indices[&6]], network.nodes[&6]);
+        for source in [1, 7, 16] {
+            let costs = dijkstra(&graph, node_indices[&source], None, |edge| *edge.weight());
+            for target in 1..=16 {
+                let expected = network.shortest_path(source, target).map(|(cost, _)| cost);
+                assert_eq!(costs.get(&node_indices[&target]).copied(), expected, "{} -> {}", source, target);
+            }
+        }
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::sync;
+
+use serde::{Deserialize, Serialize};
+
+use crate::WayTags;
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(std::io::Error),
+    Parse(String),
+    InvalidSpeed(String, f32),  // class (or option) and the rejected speed
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::Io(error) => write!(f, "could not read profile: {}", error),
+            ProfileError::Parse(message) => write!(f, "could not parse profile: {}", message),
+            ProfileError::InvalidSpeed(class, speed) => {
+                write!(f, "value for {} must be positive, got {}", class, speed)
+            }
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+impl From<std::io::Error> for ProfileError {
+    fn from(error: std::io::Error) -> ProfileError {
+        ProfileError::Io(error)
+    }
+}
+
+const MPH: f32 = 1.609_344;  // mph to km/h factor
+
+fn is_valid_speed(speed: f32) -> bool {
+    speed.is_finite() && speed > 0_f32
+}
+
+// Maps highway classes to travel speeds. Fields missing from a config file
+// keep the values of the built-in car profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedProfile {
+    pub speeds: HashMap<String, f32>,  // in km/h
+    pub default_unknown_speed: Option<f32>,  // for classes not in `speeds`, `None` skips them
+    pub blocked: Vec<String>,  // never routable, even if listed in `speeds`
+    pub implicit_maxspeeds: HashMap<String, f32>,  // e.g. "DE:urban" => 50 km/h
+    // Cost multipliers by "key=value" tag, for roads that should only be used
+    // when necessary instead of being dropped (e.g. "access=destination").
+    pub penalties: HashMap<String, f32>,
+    pub blocking_barriers: Vec<String>,  // `barrier=*` node values that can't be passed
+    pub ignore_oneway: bool,  // for pedestrians
+    // Upper bound for every arc's speed (before penalties), e.g. to simulate a
+    // general speed limit. Wins over higher `maxspeed` tags.
+    pub global_speed_cap_kmh: Option<f32>,
+    // For `route=ferry` ways without a `duration` tag, `None` leaves out ferries.
+    pub ferry_speed_kmh: Option<f32>,
+    // Multiplier for the speed of ways with a `lanes` tag, given the number
+    // of lanes, e.g. to model that wide roads move traffic faster. Not part
+    // of config files; `None` (the default) leaves speeds alone.
+    #[serde(skip)]
+    pub lanes_speed_factor: Option<LanesSpeedFactor>,
+}
+
+// A function from the number of lanes to a speed multiplier. Profiles
+// holding one are only equal if they share the same function.
+#[derive(Clone)]
+pub struct LanesSpeedFactor(sync::Arc<dyn Fn(u32) -> f32 + Send + Sync>);
+
+impl LanesSpeedFactor {
+    pub fn new(factor: impl Fn(u32) -> f32 + Send + Sync + 'static) -> LanesSpeedFactor {
+        LanesSpeedFactor(sync::Arc::new(factor))
+    }
+}
+
+impl fmt::Debug for LanesSpeedFactor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LanesSpeedFactor(..)")
+    }
+}
+
+impl PartialEq for LanesSpeedFactor {
+    fn eq(&self, other: &LanesSpeedFactor) -> bool {
+        sync::Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Default for SpeedProfile {
+    fn default() -> SpeedProfile {
+        SpeedProfile::car()
+    }
+}
+
+impl SpeedProfile {
+    pub fn car() -> SpeedProfile {
+        let speeds = [
+            ("motorway", 110_f32),
+            ("trunk", 110_f32),
+            ("primary", 70_f32),
+            ("secondary", 60_f32),
+            ("tertiary", 50_f32),
+            ("motorway_link", 50_f32),
+            ("trunk_link", 50_f32),
+            ("primary_link", 50_f32),
+            ("secondary_link", 50_f32),
+            ("road", 40_f32),
+            ("unclassified", 40_f32),
+            ("residential", 30_f32),
+            ("unsurfaced", 30_f32),
+            ("living_street", 10_f32),
+            ("service", 5_f32),
+        ];
+        SpeedProfile {
+            speeds: speeds.iter().map(|&(class, speed)| (class.to_string(), speed)).collect(),
+            default_unknown_speed: None,
+            blocked: Vec::new(),
+            implicit_maxspeeds: default_implicit_maxspeeds(),
+            penalties: [
+                ("access=destination", 2_f32),
+                ("tracktype=grade4", 2_f32),
+                ("tracktype=grade5", 3_f32),
+            ].iter().map(|&(tag, penalty)| (tag.to_string(), penalty)).collect(),
+            blocking_barriers: to_strings(&[
+                "block", "bollard", "chain", "cycle_barrier", "gate", "jersey_barrier",
+                "kissing_gate", "stile", "turnstile",
+            ]),
+            ignore_oneway: false,
+            global_speed_cap_kmh: None,
+            ferry_speed_kmh: Some(10_f32),
+            lanes_speed_factor: None,
+        }
+    }
+
+    pub fn bicycle() -> SpeedProfile {
+        let speeds = [
+            ("cycleway", 18_f32),
+            ("primary", 16_f32),
+            ("secondary", 16_f32),
+            ("tertiary", 18_f32),
+            ("primary_link", 16_f32),
+            ("secondary_link", 16_f32),
+            ("tertiary_link", 18_f32),
+            ("road", 16_f32),
+            ("unclassified", 16_f32),
+            ("residential", 18_f32),
+            ("living_street", 10_f32),
+            ("service", 12_f32),
+            ("track", 12_f32),
+            ("path", 12_f32),
+            ("unsurfaced", 12_f32),
+        ];
+        SpeedProfile {
+            speeds: speeds.iter().map(|&(class, speed)| (class.to_string(), speed)).collect(),
+            default_unknown_speed: None,
+            blocked: to_strings(&["motorway", "motorway_link", "trunk", "trunk_link"]),
+            implicit_maxspeeds: default_implicit_maxspeeds(),
+            penalties: [
+                ("tracktype=grade4", 1.5_f32),
+                ("tracktype=grade5", 2_f32),
+            ].iter().map(|&(tag, penalty)| (tag.to_string(), penalty)).collect(),
+            blocking_barriers: to_strings(&["stile", "turnstile"]),
+            ignore_oneway: false,
+            global_speed_cap_kmh: None,
+            ferry_speed_kmh: Some(10_f32),
+            lanes_speed_factor: None,
+        }
+    }
+
+    // Walking speed everywhere pedestrians are allowed, in both directions.
+    pub fn foot() -> SpeedProfile {
+        let classes = [
+            "footway", "pedestrian", "path", "steps", "living_street", "residential",
+            "service", "track", "unclassified", "road", "tertiary", "secondary", "primary",
+            "tertiary_link", "secondary_link", "primary_link", "unsurfaced",
+        ];
+        SpeedProfile {
+            speeds: classes.iter().map(|&class| (class.to_string(), 5_f32)).collect(),
+            default_unknown_speed: None,
+            blocked: to_strings(&["motorway", "motorway_link", "trunk", "trunk_link"]),
+            implicit_maxspeeds: HashMap::new(),
+            penalties: HashMap::new(),
+            blocking_barriers: Vec::new(),
+            ignore_oneway: true,
+            global_speed_cap_kmh: None,
+            ferry_speed_kmh: Some(10_f32),
+            lanes_speed_factor: None,
+        }
+    }
+
+    // Tractors and forestry vehicles: tracks are routable, slower the worse
+    // their `tracktype` (grade1 = paved/solid ... grade5 = soft), and nothing
+    // is faster than 40 km/h. Motorways and trunk roads are off limits.
+    pub fn agricultural() -> SpeedProfile {
+        let speeds = [
+            ("primary", 40_f32),
+            ("secondary", 40_f32),
+            ("tertiary", 40_f32),
+            ("primary_link", 40_f32),
+            ("secondary_link", 40_f32),
+            ("tertiary_link", 40_f32),
+            ("road", 30_f32),
+            ("unclassified", 30_f32),
+            ("residential", 30_f32),
+            ("unsurfaced", 25_f32),
+            ("track", 25_f32),
+            ("living_street", 10_f32),
+            ("service", 10_f32),
+        ];
+        SpeedProfile {
+            speeds: speeds.iter().map(|&(class, speed)| (class.to_string(), speed)).collect(),
+            default_unknown_speed: None,
+            blocked: to_strings(&["motorway", "motorway_link", "trunk", "trunk_link"]),
+            implicit_maxspeeds: default_implicit_maxspeeds(),
+            penalties: [
+                ("tracktype=grade2", 1.25_f32),
+                ("tracktype=grade3", 1.5_f32),
+                ("tracktype=grade4", 2_f32),
+                ("tracktype=grade5", 3_f32),
+            ].iter().map(|&(tag, penalty)| (tag.to_string(), penalty)).collect(),
+            blocking_barriers: to_strings(&["block", "bollard", "jersey_barrier", "stile", "turnstile"]),
+            ignore_oneway: false,
+            global_speed_cap_kmh: None,
+            ferry_speed_kmh: Some(10_f32),
+            lanes_speed_factor: None,
+        }
+    }
+
+    pub fn from_toml_file(filename: &str) -> Result<SpeedProfile, ProfileError> {
+        SpeedProfile::from_toml_str(&fs::read_to_string(filename)?)
+    }
+
+    pub fn from_toml_str(config: &str) -> Result<SpeedProfile, ProfileError> {
+        let profile: SpeedProfile = toml::from_str(config)
+            .map_err(|error| ProfileError::Parse(error.to_string()))?;
+        profile.validate()
+    }
+
+    pub fn from_json_str(config: &str) -> Result<SpeedProfile, ProfileError> {
+        let profile: SpeedProfile = serde_json::from_str(config)
+            .map_err(|error| ProfileError::Parse(error.to_string()))?;
+        profile.validate()
+    }
+
+    // Sets the speed of a single highway class, keeping all others. Meant
+    // for classes whose real speeds vary a lot by region, like "road" (not
+    // yet classified), "unclassified" or "service". Classes in `blocked`
+    // stay blocked.
+    pub fn with_speed_kmh(mut self, class: &str, speed: f32) -> Result<SpeedProfile, ProfileError> {
+        if !is_valid_speed(speed) {
+            return Err(ProfileError::InvalidSpeed(class.to_string(), speed));
+        }
+        self.speeds.insert(class.to_string(), speed);
+        Ok(self)
+    }
+
+    fn validate(self) -> Result<SpeedProfile, ProfileError> {
+        for (class, &speed) in &self.speeds {
+            if !is_valid_speed(speed) {
+                return Err(ProfileError::InvalidSpeed(class.clone(), speed));
+            }
+        }
+        for (code, &speed) in &self.implicit_maxspeeds {
+            if !is_valid_speed(speed) {
+                return Err(ProfileError::InvalidSpeed(code.clone(), speed));
+            }
+        }
+        for (tag, &penalty) in &self.penalties {
+            if !is_valid_speed(penalty) {
+                return Err(ProfileError::InvalidSpeed(tag.clone(), penalty));
+            }
+        }
+        if let Some(speed) = self.default_unknown_speed {
+            if !is_valid_speed(speed) {
+                return Err(ProfileError::InvalidSpeed("default_unknown_speed".to_string(), speed));
+            }
+        }
+        if let Some(speed) = self.global_speed_cap_kmh {
+            if !is_valid_speed(speed) {
+                return Err(ProfileError::InvalidSpeed("global_speed_cap_kmh".to_string(), speed));
+            }
+        }
+        if let Some(speed) = self.ferry_speed_kmh {
+            if !is_valid_speed(speed) {
+                return Err(ProfileError::InvalidSpeed("ferry_speed_kmh".to_string(), speed));
+            }
+        }
+        Ok(self)
+    }
+
+    // Speed in km/h for a highway class, `None` if it is not routable.
+    pub fn speed_kmh(&self, class: &str) -> Option<f32> {
+        if self.blocked.iter().any(|blocked| blocked == class) {
+            return None;
+        }
+        self.speeds.get(class).copied().or(self.default_unknown_speed)
+    }
+
+    // Product of all penalties matching the way's tags, 1 if none does.
+    pub fn penalty(&self, tags: &WayTags) -> f32 {
+        if self.penalties.is_empty() {
+            return 1_f32;
+        }
+        tags.pairs()
+            .filter_map(|(key, value)| self.penalties.get(&format!("{}={}", key, value)))
+            .product()
+    }
+
+    // `speed` limited to `global_speed_cap_kmh`.
+    pub fn capped_kmh(&self, speed: f32) -> f32 {
+        self.global_speed_cap_kmh.map_or(speed, |cap| speed.min(cap))
+    }
+
+    // `lanes_speed_factor` for a `lanes` value, 1 if it doesn't apply or
+    // returns something unusable.
+    pub fn lanes_factor(&self, lanes: &str) -> f32 {
+        let Some(factor) = &self.lanes_speed_factor else { return 1_f32 };
+        lanes.trim().parse::<u32>().ok()
+            .map(|lanes| (factor.0)(lanes))
+            .filter(|&factor| is_valid_speed(factor))
+            .unwrap_or(1_f32)
+    }
+
+    pub fn blocks_barrier(&self, barrier: &str) -> bool {
+        self.blocking_barriers.iter().any(|blocking| blocking == barrier)
+    }
+
+    // Interprets a `maxspeed` value: plain km/h ("50"), miles ("30 mph") or an
+    // implicit legal limit ("DE:urban"). `None` for anything else ("none",
+    // "signals", unknown codes), so the highway class default applies.
+    pub fn maxspeed_kmh(&self, maxspeed: &str) -> Option<f32> {
+        let maxspeed = maxspeed.trim();
+        let speed = if let Some(mph) = maxspeed.strip_suffix("mph") {
+            mph.trim().parse::<f32>().ok().map(|mph| mph * MPH)
+        } else {
+            maxspeed.parse::<f32>().ok()
+        };
+        speed.or_else(|| self.implicit_maxspeeds.get(maxspeed).copied())
+            .filter(|&speed| is_valid_speed(speed))
+    }
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn default_implicit_maxspeeds() -> HashMap<String, f32> {
+    let speeds = [
+        ("AT:urban", 50_f32),
+        ("AT:rural", 100_f32),
+        ("AT:motorway", 130_f32),
+        ("BE:urban", 50_f32),
+        ("BE:rural", 70_f32),
+        ("BE:motorway", 120_f32),
+        ("CH:urban", 50_f32),
+        ("CH:rural", 80_f32),
+        ("CH:motorway", 120_f32),
+        ("DE:living_street", 7_f32),
+        ("DE:urban", 50_f32),
+        ("DE:rural", 100_f32),
+        ("FR:urban", 50_f32),
+        ("FR:rural", 80_f32),
+        ("FR:motorway", 130_f32),
+        ("GB:nsl_single", 60_f32 * MPH),
+        ("GB:nsl_dual", 70_f32 * MPH),
+        ("GB:motorway", 70_f32 * MPH),
+        ("LU:urban", 50_f32),
+        ("LU:rural", 90_f32),
+        ("LU:motorway", 130_f32),
+        ("NL:urban", 50_f32),
+        ("NL:rural", 80_f32),
+        ("NL:motorway", 100_f32),
+    ];
+    speeds.iter().map(|&(code, speed)| (code.to_string(), speed)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, RoadNetwork};
+
+    const CONFIG: &str = "default_unknown_speed = 18.0\nblocked = [\"primary\"]\n\n[speeds]\nresidential = 36.0\n";
+
+    #[test]
+    fn config_file_speeds_take_effect() {
+        let filename = std::env::temp_dir().join("hello-rust-profile-test.toml");
+        fs::write(&filename, CONFIG).unwrap();
+        let profile = SpeedProfile::from_toml_file(filename.to_str().unwrap()).unwrap();
+        fs::remove_file(&filename).unwrap();
+        assert_eq!(profile.speed_kmh("residential"), Some(36_f32));
+        assert_eq!(profile.speed_kmh("track"), Some(18_f32));
+        assert_eq!(profile.speed_kmh("primary"), None);
+
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49_f32, 7.02), (4, 49_f32, 7.03)],
+            &[(10, &[1, 2], &[("highway", "residential")]), (11, &[2, 3], &[("highway", "track")]),
+              (12, &[3, 4], &[("highway", "primary")])],
+        );
+        let network = fixtures::parse_into(RoadNetwork::new().with_speed_profile(profile), &xml);
+        let expected = (network.distance(1, 2) / 10_f32) as usize + (network.distance(2, 3) / 5_f32) as usize;
+        assert_eq!(network.shortest_path(1, 3).map(|(cost, _)| cost), Some(expected));
+        assert_eq!(network.shortest_path(1, 4), None);
+    }
+
+    #[test]
+    fn json_config_and_invalid_speeds() {
+        let profile = SpeedProfile::from_json_str(r#"{"speeds": {"residential": 20}}"#).unwrap();
+        assert_eq!(profile.speed_kmh("residential"), Some(20_f32));
+        // fields missing from the file keep the car profile's values
+        assert_eq!(profile.blocked, SpeedProfile::car().blocked);
+        assert_eq!(profile.default_unknown_speed, SpeedProfile::car().default_unknown_speed);
+        assert!(matches!(
+            SpeedProfile::from_toml_str("[speeds]\nresidential = -5.0\n"),
+            Err(ProfileError::InvalidSpeed(class, _)) if class == "residential"
+        ));
+        assert!(matches!(SpeedProfile::from_json_str("{\"speeds\": 3}"), Err(ProfileError::Parse(_))));
+    }
+
+    #[test]
+    fn implicit_maxspeed_codes() {
+        let profile = SpeedProfile::car();
+        assert_eq!(profile.maxspeed_kmh("DE:urban"), Some(50_f32));
+        assert_eq!(profile.maxspeed_kmh("FR:rural"), Some(80_f32));
+        assert_eq!(profile.maxspeed_kmh(" 70 "), Some(70_f32));
+        assert!((profile.maxspeed_kmh("30 mph").unwrap() - 48.28).abs() < 0.01);
+        assert_eq!(profile.maxspeed_kmh("XX:urban"), None);
+        assert_eq!(profile.maxspeed_kmh("none"), None);
+    }
+
+    #[test]
+    fn implicit_maxspeed_sets_arc_cost() {
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49_f32, 7.02)];
+        let xml = fixtures::osm_xml(&nodes, &[
+            (10, &[1, 2], &[("highway", "primary"), ("maxspeed", "DE:urban")]),
+            (11, &[2, 3], &[("highway", "primary"), ("maxspeed", "XX:unknown")]),
+        ]);
+        let network = fixtures::parse(&xml);
+        let cost = |from, to| network.shortest_path(from, to).unwrap().0;
+        assert_eq!(cost(1, 2), (network.distance(1, 2) / (50_f32 / 3.6)) as usize);
+        // unknown codes fall back to the class default
+        let primary = SpeedProfile::car().speed_kmh("primary").unwrap();
+        assert_eq!(cost(2, 3), (network.distance(2, 3) / (primary / 3.6)) as usize);
+    }
+
+    // Node 3 lies `detour_lat` north of the middle between 1 and 2, which are
+    // connected directly by an `access=destination` road.
+    fn penalized_shortcut(detour_lat: f32) -> RoadNetwork {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49_f32 + detour_lat, 7.005)],
+            &[(10, &[1, 2], &[("highway", "residential"), ("access", "destination")]),
+              (11, &[1, 3, 2], &[("highway", "residential")])],
+        );
+        let mut profile = SpeedProfile::car();
+        profile.penalties.insert("access=destination".to_string(), 3_f32);
+        fixtures::parse_into(RoadNetwork::new().with_speed_profile(profile), &xml)
+    }
+
+    #[test]
+    fn penalties_inflate_instead_of_excluding() {
+        // a 5 % longer detour beats the penalized shortcut
+        let network = penalized_shortcut(0.001);
+        assert_eq!(network.shortest_path(1, 2).map(|(_, path)| path), Some(vec![1, 3, 2]));
+        // a 6 times longer one doesn't
+        let network = penalized_shortcut(0.02);
+        let (cost, path) = network.shortest_path(1, 2).unwrap();
+        assert_eq!(path, vec![1, 2]);
+        let residential = SpeedProfile::car().speed_kmh("residential").unwrap() / 3.6;
+        assert_eq!(cost, (network.distance(1, 2) / (residential / 3_f32)) as usize);
+    }
+
+    #[test]
+    fn agricultural_track_grades_order_costs() {
+        // five equally long tracks from node `10 * grade` to `10 * grade + 1`
+        let nodes: Vec<(isize, f32, f32)> = (1..=5)
+            .flat_map(|grade| [(grade * 10, 49_f32, 7_f32 + grade as f32 * 0.01), (grade * 10 + 1, 49.01, 7_f32 + grade as f32 * 0.01)])
+            .collect();
+        let refs = [[10, 11], [20, 21], [30, 31], [40, 41], [50, 51]];
+        let tags = ["grade1", "grade2", "grade3", "grade4", "grade5"].map(|grade| [("highway", "track"), ("tracktype", grade)]);
+        let ways: Vec<fixtures::Way> = (0..5).map(|position| (100 + position as isize, &refs[position][..], &tags[position][..])).collect();
+        let network = fixtures::parse_into(
+            RoadNetwork::new().with_speed_profile(SpeedProfile::agricultural()), &fixtures::osm_xml(&nodes, &ways)
+        );
+        let costs: Vec<usize> = refs.iter().map(|pair| network.shortest_path(pair[0], pair[1]).unwrap().0).collect();
+        assert!(costs.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", costs);
+        assert_eq!(SpeedProfile::agricultural().speed_kmh("motorway"), None);
+    }
+
+    #[test]
+    fn global_speed_cap_only_raises_costs() {
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49_f32, 7.02), (4, 49_f32, 7.03)];
+        let ways: [fixtures::Way; 3] = [
+            (10, &[1, 2], &[("highway", "motorway"), ("maxspeed", "130")]),
+            (11, &[2, 3], &[("highway", "residential"), ("maxspeed", "20")]),
+            (12, &[3, 4], &[("highway", "living_street")]),
+        ];
+        let xml = fixtures::osm_xml(&nodes, &ways);
+        let uncapped = fixtures::parse(&xml);
+        let profile = SpeedProfile {global_speed_cap_kmh: Some(30_f32), ..SpeedProfile::car()};
+        let capped = fixtures::parse_into(RoadNetwork::new().with_speed_profile(profile), &xml);
+        let (before, after) = (uncapped._sorted_arcs(), capped._sorted_arcs());
+        assert_eq!(before.len(), after.len());
+        for (arc, capped_arc) in before.iter().zip(&after) {
+            assert_eq!((arc.0, arc.1), (capped_arc.0, capped_arc.1));
+            assert!(capped_arc.2 >= arc.2, "{:?} < {:?}", capped_arc, arc);
+        }
+        let motorway = capped.shortest_path(1, 2).unwrap().0;
+        assert_eq!(motorway, (capped.distance(1, 2) / (30_f32 / 3.6)) as usize);
+        // a lower maxspeed wins over the cap
+        assert_eq!(capped.shortest_path(2, 3), uncapped.shortest_path(2, 3));
+    }
+
+    #[test]
+    fn lanes_scale_the_speed() {
+        // two residential roads of the same length, with 4 and 1 lanes
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49.01, 7_f32), (4, 49.01, 7.01)];
+        let ways: [fixtures::Way; 2] = [
+            (10, &[1, 2], &[("highway", "residential"), ("lanes", "4")]),
+            (11, &[3, 4], &[("highway", "residential"), ("lanes", "1")]),
+        ];
+        let xml = fixtures::osm_xml(&nodes, &ways);
+        let costs = |network: &RoadNetwork| (fixtures::path_cost(network, &[1, 2]).unwrap(), fixtures::path_cost(network, &[3, 4]).unwrap());
+        // off by default
+        let (four, one) = costs(&fixtures::parse(&xml));
+        assert!(four.abs_diff(one) <= 1);
+        let profile = SpeedProfile {
+            lanes_speed_factor: Some(LanesSpeedFactor::new(|lanes| 1_f32 + 0.1 * (lanes as f32 - 1_f32))),
+            ..SpeedProfile::car()
+        };
+        let (four, one) = costs(&fixtures::parse_into(RoadNetwork::new().with_speed_profile(profile.clone()), &xml));
+        assert!((one as f32 / four as f32 - 1.3).abs() < 0.02, "{} {}", four, one);
+        assert_eq!((profile.lanes_factor("two"), profile.lanes_factor(" 2 ")), (1_f32, 1.1));
+        let broken = SpeedProfile {lanes_speed_factor: Some(LanesSpeedFactor::new(|_| -1_f32)), ..SpeedProfile::car()};
+        assert_eq!(broken.lanes_factor("4"), 1_f32);
+    }
+
+    #[test]
+    fn overridden_service_speed_only_changes_service_roads() {
+        let nodes = [(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49_f32, 7.02), (4, 49_f32, 7.03)];
+        let ways: [fixtures::Way; 3] = [
+            (10, &[1, 2], &[("highway", "service")]),
+            (11, &[2, 3], &[("highway", "road")]),
+            (12, &[3, 4], &[("highway", "unclassified")]),
+        ];
+        let xml = fixtures::osm_xml(&nodes, &ways);
+        let profile = SpeedProfile::car().with_speed_kmh("service", 20_f32).unwrap();
+        assert_eq!(profile.speed_kmh("service"), Some(20_f32));
+        let before = fixtures::parse(&xml);
+        let after = fixtures::parse_into(RoadNetwork::new().with_speed_profile(profile), &xml);
+        assert_eq!(fixtures::path_cost(&after, &[1, 2]), Some((after.distance(1, 2) / (20_f32 / 3.6)) as usize));
+        assert_ne!(fixtures::path_cost(&after, &[1, 2]), fixtures::path_cost(&before, &[1, 2]));
+        for pair in [[2, 3], [3, 4], [4, 3]] {
+            assert_eq!(fixtures::path_cost(&after, &pair), fixtures::path_cost(&before, &pair));
+        }
+        assert!(matches!(
+            SpeedProfile::car().with_speed_kmh("road", 0_f32),
+            Err(ProfileError::InvalidSpeed(class, _)) if class == "road"
+        ));
+        // blocked classes stay blocked
+        let profile = SpeedProfile {blocked: vec!["service".to_string()], ..SpeedProfile::car()};
+        assert_eq!(profile.with_speed_kmh("service", 20_f32).unwrap().speed_kmh("service"), None);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{Point, RoadNetwork};
+
+pub const REPL_USAGE: &str = "commands: route <from_id> <to_id> | near <lat> <lon> | stats | quit";
+
+impl RoadNetwork {
+    // Answers one command of the interactive mode with a line or two of
+    // text: `route <from_id> <to_id>`, `near <lat> <lon>` or `stats`.
+    // Anything else gets `REPL_USAGE`; `quit` is up to the caller.
+    pub fn repl_command(&self, command: &str) -> String {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["route", from, to] => {
+                let (Ok(from), Ok(to)) = (from.parse::<isize>(), to.parse::<isize>()) else {
+                    return format!("node IDs must be integers\n{}", REPL_USAGE);
+                };
+                match self.shortest_path(from, to) {
+                    Some((cost, path)) => format!("cost: {}s over {} nodes\npath: {:?}", cost, path.len(), path),
+                    None => format!("no route from {} to {}", from, to),
+                }
+            }
+            ["near", lat, lon] => {
+                let Ok(location) = format!("{},{}", lat, lon).parse::<Point>() else {
+                    return format!("expected coordinates in degrees\n{}", REPL_USAGE);
+                };
+                match self.nearest_routable_node(location) {
+                    Some(osm_id) => {
+                        let meters = self.distance_model.distance(self.nodes[&osm_id], location);
+                        format!("node {} at {:.0}m", osm_id, meters)
+                    }
+                    None => "no routable nodes".to_string(),
+                }
+            }
+            ["stats"] => format!("nodes: {}, arcs: {}", self.node_count(), self.arc_count()),
+            _ => REPL_USAGE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn commands_answer_in_a_line_or_two() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, false), (10, 11, true)]);
+        let (cost, _) = network.shortest_path(1, 3).unwrap();
+        assert_eq!(network.repl_command("route 1 3"), format!("cost: {}s over 3 nodes\npath: [1, 2, 3]", cost));
+        assert_eq!(network.repl_command("  route   3 1 "), "no route from 3 to 1");
+        assert_eq!(network.repl_command("route 1 99"), "no route from 1 to 99");
+        assert_eq!(network.repl_command("near 49.0001 7.0021"), "node 2 at 13m");
+        assert_eq!(network.repl_command("stats"), "nodes: 5, arcs: 5");
+        assert_eq!(RoadNetwork::new().repl_command("near 49 7"), "no routable nodes");
+    }
+
+    #[test]
+    fn malformed_commands_get_the_usage() {
+        let network = fixtures::network(&[(1, 2, true)]);
+        for command in ["", "help", "route 1", "route 1 2 3", "stats now", "quit"] {
+            assert_eq!(network.repl_command(command), REPL_USAGE, "{:?}", command);
+        }
+        assert_eq!(network.repl_command("route a 2"), format!("node IDs must be integers\n{}", REPL_USAGE));
+        assert_eq!(network.repl_command("near 49 east"), format!("expected coordinates in degrees\n{}", REPL_USAGE));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::{Point, RoadNetwork, Route};
+
+const ROUND_TRIP_ATTEMPTS: usize = 20;
+const ROUND_TRIP_TOLERANCE: f32 = 0.15;  // accepted deviation from the budget
+// Roads are longer than the circle they follow, about by this factor.
+const DETOUR_FACTOR: f32 = 1.3;
+
+// SplitMix64, enough to pick directions reproducibly without a dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    // Uniform in [0, 1).
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1_u64 << 24) as f32
+    }
+}
+
+impl RoadNetwork {
+    // A loop from `start` back to `start` taking about `target_seconds`: two
+    // via nodes are snapped to a circle through `start` (in a direction
+    // picked from `seed`), and the legs between them are shortest paths.
+    // Loops costing more than 15% off the budget are retried in other
+    // directions with the circle resized, as are loops that run along the
+    // same roads in both directions for more than half their length. `None`
+    // if no attempt succeeded or `start` has no coordinates.
+    pub fn round_trip(&self, start: isize, target_seconds: usize, seed: u64) -> Option<Route> {
+        self.get_index(start)?;
+        let origin = *self.nodes.get(&start)?;
+        let speed = self._average_speed()?;
+        let mut radius = speed * target_seconds as f32 / (2_f32 * std::f32::consts::PI * DETOUR_FACTOR);
+        let mut random = SplitMix64(seed);
+        for _ in 0..ROUND_TRIP_ATTEMPTS {
+            let heading = random.next_f32() * 2_f32 * std::f32::consts::PI;
+            let center = offset(origin, radius, heading);
+            let vias: Vec<isize> = [1_f32, 2_f32].iter()
+                .map(|third| heading + std::f32::consts::PI + third * 2_f32 * std::f32::consts::PI / 3_f32)
+                .filter_map(|angle| self.nearest_routable_node(offset(center, radius, angle)))
+                .collect();
+            let [first, second] = vias[..] else { return None };
+            let Some((cost, path)) = self.route_with_waypoints(&[start, first, second, start]) else {
+                continue;
+            };
+            let deviation = (cost as f32 - target_seconds as f32) / target_seconds.max(1) as f32;
+            if deviation.abs() <= ROUND_TRIP_TOLERANCE && !self._mostly_out_and_back(&path) {
+                return Some(Route {cost, geometry: self._geometry(&path), path, snap_distances: (0_f32, 0_f32)});
+            }
+            if cost > 0 {
+                radius *= (target_seconds as f32 / cost as f32).clamp(0.5_f32, 2_f32);
+            }
+        }
+        None
+    }
+
+    // Meters per second of cost over all arcs between nodes with coordinates.
+    fn _average_speed(&self) -> Option<f32> {
+        let (mut meters, mut seconds) = (0_f64, 0_f64);
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            let Some(from) = self.point_by_index(index) else { continue };
+            for arc in arcs {
+                let Some(to) = self.point_by_index(arc.index) else { continue };
+                meters += self.distance_model.distance(from, to) as f64;
+                seconds += arc.cost as f64;
+            }
+        }
+        Some((meters / seconds) as f32).filter(|speed| speed.is_finite() && *speed > 0_f32)
+    }
+
+    // Whether more than half of the path's length lies on edges that it
+    // travels in both directions.
+    fn _mostly_out_and_back(&self, path: &[isize]) -> bool {
+        let mut directions: HashMap<(isize, isize), (bool, bool)> = HashMap::new();
+        for pair in path.windows(2) {
+            let entry = directions.entry((pair[0].min(pair[1]), pair[0].max(pair[1]))).or_default();
+            if pair[0] < pair[1] { entry.0 = true } else { entry.1 = true }
+        }
+        let (mut total, mut repeated) = (0_f64, 0_f64);
+        for pair in path.windows(2) {
+            let (Some(&a), Some(&b)) = (self.nodes.get(&pair[0]), self.nodes.get(&pair[1])) else { continue };
+            let meters = self.distance_model.distance(a, b) as f64;
+            total += meters;
+            if directions[&(pair[0].min(pair[1]), pair[0].max(pair[1]))] == (true, true) {
+                repeated += meters;
+            }
+        }
+        repeated > total / 2_f64
+    }
+}
+
+// The point `meters` away from `point` in direction `angle` (radians
+// counterclockwise from east), in a local flat approximation.
+fn offset(point: Point, meters: f32, angle: f32) -> Point {
+    let lat_degrees = meters * angle.sin() / 111_229_f32;
+    let lon_degrees = meters * angle.cos() / (111_229_f32 * point.lat.to_radians().cos().max(0.01_f32));
+    Point {lat: point.lat + lat_degrees, lon: point.lon + lon_degrees}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    #[test]
+    fn loops_on_a_grid_within_the_budget() {
+        // 111 m north-south and 73 m east-west between neighbors at 10 m/s,
+        // so a loop of 400 s spans about a third of the grid
+        let network = fixtures::grid(20);
+        let start = 10 * 20 + 11;
+        for seed in [1, 2, 3] {
+            let route = network.round_trip(start, 400, seed).unwrap();
+            assert_eq!((route.path[0], route.path[route.path.len() - 1]), (start, start));
+            assert!((340..=460).contains(&route.cost), "{}", route.cost);
+            assert_eq!(fixtures::path_cost(&network, &route.path), Some(route.cost));
+            assert!(!network._mostly_out_and_back(&route.path));
+            assert_eq!(route.geometry.len(), route.path.len());
+            assert_eq!(network.round_trip(start, 400, seed), Some(route));
+        }
+        assert_eq!(network.round_trip(401, 400, 1), None);
+    }
+
+    #[test]
+    fn out_and_back_is_detected() {
+        let network = fixtures::grid(3);
+        assert!(network._mostly_out_and_back(&[1, 2, 3, 2, 1]));
+        assert!(!network._mostly_out_and_back(&[1, 2, 5, 4, 1]));
+        // a short spur on a loop is fine
+        assert!(!network._mostly_out_and_back(&[1, 2, 3, 6, 5, 8, 5, 4, 1]));
+    }
+}
@@ -0,0 +1,905 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::{Arc, OsmError, Point, RoadNetwork, MAX_ARC_COST};
+
+// How far (in meters) a query point may be from the road network to be snapped.
+pub const DEFAULT_SNAP_RADIUS: f32 = 500_f32;
+// Nodes tried per query point by `route_between_points`.
+const SNAP_CANDIDATES: usize = 5;
+// Turns sharper than this (in degrees) count as turning around, see
+// `route_with_u_turn_penalty`.
+const U_TURN_ANGLE: f32 = 150_f32;
+
+// What to do with query points that are far from the road network.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SnapPolicy {
+    StrictWithin(f32),  // fail if no node is within that many meters
+    NearestAlways,  // take the closest node however far away it is
+    // The closest node like `NearestAlways`, but one beyond the radius is
+    // marked as a fallback (`Snap::fallback`, see `snap_detailed`).
+    WithinThenNearest(f32),
+}
+
+// Strict, with `DEFAULT_SNAP_RADIUS`.
+impl Default for SnapPolicy {
+    fn default() -> SnapPolicy {
+        SnapPolicy::StrictWithin(DEFAULT_SNAP_RADIUS)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoutingError {
+    UnknownNode(isize),
+    NoRoute,
+    LimitExceeded,  // settled more than `max_settled_nodes` without reaching the target
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoutingError::UnknownNode(osm_id) => write!(f, "node {} is not part of the network", osm_id),
+            RoutingError::NoRoute => write!(f, "no route found"),
+            RoutingError::LimitExceeded => write!(f, "search limit exceeded"),
+        }
+    }
+}
+
+impl Error for RoutingError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub cost: usize,  // in seconds
+    pub path: Vec<isize>,
+    pub geometry: Vec<Point>,
+    pub snap_distances: (f32, f32),  // meters from the query points to the first and last node
+}
+
+// "34s over 5 nodes from 1 to 3", OSM IDs.
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.path.first(), self.path.last()) {
+            (Some(first), Some(last)) => {
+                write!(f, "{}s over {} nodes from {} to {}", self.cost, self.path.len(), first, last)
+            }
+            _ => write!(f, "{}s over 0 nodes", self.cost),
+        }
+    }
+}
+
+// A node a query point was snapped to, see `snap_detailed`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Snap {
+    pub osm_id: isize,
+    pub distance: f32,  // meters from the query point
+    pub fallback: bool,  // `WithinThenNearest` found nothing within its radius
+}
+
+// Time and distance of the fastest route, see `route_cost_breakdown`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostBreakdown {
+    pub cost: usize,  // in seconds
+    pub meters: f64,
+    pub average_kmh: Option<f64>,  // `None` for a route that costs nothing
+    pub path: Vec<isize>,
+}
+
+pub(crate) struct Search {
+    pub costs: Vec<usize>,  // usize::MAX for unreached nodes
+    pub predecessors: Vec<Option<usize>>,
+}
+
+impl Search {
+    pub fn path_to(&self, target: usize) -> Option<Vec<usize>> {
+        if self.costs[target] == usize::MAX {
+            return None;
+        }
+        let mut path = vec![target];
+        let mut current = target;
+        while let Some(previous) = self.predecessors[current] {
+            path.push(previous);
+            current = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+// All shortest paths from one source, computed by a single Dijkstra run.
+pub struct ShortestPathTree<'a> {
+    network: &'a RoadNetwork,
+    source: isize,
+    search: Search,
+    max_cost: usize,  // nodes beyond count as not reached
+}
+
+impl<'a> ShortestPathTree<'a> {
+    pub fn source(&self) -> isize {
+        self.source
+    }
+
+    fn _cost(&self, index: usize) -> Option<usize> {
+        Some(self.search.costs[index]).filter(|&cost| cost <= self.max_cost)
+    }
+
+    pub fn cost_to(&self, target: isize) -> Option<usize> {
+        self._cost(self.network.get_index(target)?)
+    }
+
+    // Same result as `RoadNetwork::shortest_path(source, target)`, in O(path length).
+    pub fn path_to(&self, target: isize) -> Option<(usize, Vec<isize>)> {
+        let index = self.network.get_index(target)?;
+        let cost = self._cost(index)?;
+        let path = self.search.path_to(index)?;
+        Some((cost, self.network._to_osm_ids(path)))
+    }
+
+    // Every reached node as `(osm_id, cost, predecessor)` in index order, the
+    // source without a predecessor.
+    pub fn reached(&self) -> impl Iterator<Item = (isize, usize, Option<isize>)> + '_ {
+        (0..self.search.costs.len()).filter_map(move |index| {
+            let cost = self._cost(index)?;
+            let predecessor = self.search.predecessors[index].map(|previous| self.network.osm_ids[previous]);
+            Some((self.network.osm_ids[index], cost, predecessor))
+        })
+    }
+
+    // GeoJSON FeatureCollection with one LineString per tree edge, from the
+    // predecessor to the node, with the cost of reaching the node as property
+    // (e.g. to color travel time contours). Edges with a node without
+    // coordinates are left out.
+    pub fn write_geojson<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, r#"{{"type":"FeatureCollection","features":["#)?;
+        let mut first = true;
+        for (osm_id, cost, predecessor) in self.reached() {
+            let Some(predecessor) = predecessor else { continue };
+            let (Some(from), Some(to)) = (self.network.nodes.get(&predecessor), self.network.nodes.get(&osm_id)) else {
+                continue;
+            };
+            if !first {
+                write!(writer, ",")?;
+            }
+            first = false;
+            write!(
+                writer,
+                concat!(
+                    "\n", r#"{{"type":"Feature","geometry":{{"type":"LineString","#,
+                    r#""coordinates":[[{},{}],[{},{}]]}},"#,
+                    r#""properties":{{"from":{},"to":{},"cost":{}}}}}"#
+                ),
+                from.lon, from.lat, to.lon, to.lat, predecessor, osm_id, cost
+            )?;
+        }
+        writeln!(writer, "\n]}}")
+    }
+}
+
+impl RoadNetwork {
+    pub(crate) fn _index_of(&self, osm_id: isize) -> Result<usize, RoutingError> {
+        self.get_index(osm_id).ok_or(RoutingError::UnknownNode(osm_id))
+    }
+
+    // Plain Dijkstra from `source`, stopping early once `target` is settled or
+    // the next node would cost more than `max_cost` (nodes beyond it may still
+    // have tentative costs). `arc_cost` receives the tail index, the cost of
+    // reaching it and the arc and may return `None` to skip the arc entirely.
+    pub(crate) fn _dijkstra<F>(
+        &self, source: usize, target: Option<usize>, max_settled_nodes: Option<usize>,
+        max_cost: Option<usize>, arc_cost: F
+    ) -> Result<Search, RoutingError>
+    where F: Fn(usize, usize, &Arc) -> Option<usize>
+    {
+        let mut search = Search {
+            costs: vec![usize::MAX; self.adjacent_arcs.len()],
+            predecessors: vec![None; self.adjacent_arcs.len()],
+        };
+        let mut settled = vec![false; self.adjacent_arcs.len()];
+        let mut settled_count = 0;
+        let mut queue: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        search.costs[source] = 0;
+        queue.push(Reverse((0, source)));
+
+        while let Some(Reverse((cost, index))) = queue.pop() {
+            if settled[index] {
+                continue;
+            }
+            if max_cost.is_some_and(|max_cost| cost > max_cost) {
+                break;
+            }
+            settled[index] = true;
+            if Some(index) == target {
+                break;
+            }
+            settled_count += 1;
+            if max_settled_nodes.is_some_and(|limit| settled_count > limit) {
+                return Err(RoutingError::LimitExceeded);
+            }
+            for arc in &self.adjacent_arcs[index] {
+                let Some(arc_cost) = arc_cost(index, cost, arc) else { continue };
+                let new_cost = cost.saturating_add(arc_cost);
+                if new_cost < search.costs[arc.index] {
+                    search.costs[arc.index] = new_cost;
+                    search.predecessors[arc.index] = Some(index);
+                    queue.push(Reverse((new_cost, arc.index)));
+                }
+            }
+        }
+        Ok(search)
+    }
+
+    pub(crate) fn _to_osm_ids(&self, path: Vec<usize>) -> Vec<isize> {
+        path.into_iter().map(|index| self.osm_ids[index]).collect()
+    }
+
+    pub fn shortest_path(&self, from: isize, to: isize) -> Option<(usize, Vec<isize>)> {
+        self.shortest_path_limited(from, to, None).ok()
+    }
+
+    // Like `shortest_path`, but fails with `RoutingError::LimitExceeded` once
+    // more than `max_settled_nodes` nodes were settled without reaching `to`.
+    pub fn shortest_path_limited(
+        &self, from: isize, to: isize, max_settled_nodes: Option<usize>
+    ) -> Result<(usize, Vec<isize>), RoutingError> {
+        let source = self._index_of(from)?;
+        let target = self._index_of(to)?;
+        let search = self._dijkstra(source, Some(target), max_settled_nodes, None, |_, _, arc| Some(arc.cost))?;
+        let path = search.path_to(target).ok_or(RoutingError::NoRoute)?;
+        Ok((search.costs[target], self._to_osm_ids(path)))
+    }
+
+    // Routes as if every arc for which `filter` returns false did not exist,
+    // e.g. to model temporary closures without touching the network.
+    pub fn shortest_path_filtered<F>(&self, from: isize, to: isize, filter: F) -> Option<(usize, Vec<isize>)>
+    where F: Fn(&Arc) -> bool
+    {
+        let source = self.get_index(from)?;
+        let target = self.get_index(to)?;
+        let search = self._dijkstra(
+            source, Some(target), None, None, |_, _, arc| Some(arc.cost).filter(|_| filter(arc))
+        ).ok()?;
+        let path = search.path_to(target)?;
+        Some((search.costs[target], self._to_osm_ids(path)))
+    }
+
+    // Like `shortest_path`, but turning around (tail -> head -> tail, or
+    // back by more than `U_TURN_ANGLE`, e.g. into the other carriageway)
+    // costs `penalty` seconds extra. `arrived_from` is the node travelled from just
+    // before `from`, e.g. the heading of a vehicle whose position was snapped
+    // to `from`; without the penalty the route would often start by going
+    // straight back. Searches over arcs instead of nodes, since the cost of
+    // an arc depends on how its tail was reached. The returned cost doesn't
+    // include penalties.
+    pub fn route_with_u_turn_penalty(
+        &self, from: isize, to: isize, arrived_from: Option<isize>, penalty: usize
+    ) -> Option<(usize, Vec<isize>)> {
+        let source = self.get_index(from)?;
+        let target = self.get_index(to)?;
+        // state `offsets[tail] + position` is "arrived over that arc", the
+        // extra last state is "at the source, coming from `arrived_from`"
+        let mut offsets = Vec::with_capacity(self.adjacent_arcs.len());
+        let mut state_count = 0;
+        for arcs in &self.adjacent_arcs {
+            offsets.push(state_count);
+            state_count += arcs.len();
+        }
+        let start = state_count;
+        let mut tails = vec![usize::MAX; state_count + 1];
+        let mut heads = vec![source; state_count + 1];
+        for (tail, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for (position, arc) in arcs.iter().enumerate() {
+                tails[offsets[tail] + position] = tail;
+                heads[offsets[tail] + position] = arc.index;
+            }
+        }
+        tails[start] = arrived_from.and_then(|osm_id| self.get_index(osm_id)).unwrap_or(usize::MAX);
+
+        let mut costs = vec![usize::MAX; state_count + 1];
+        let mut predecessors: Vec<Option<usize>> = vec![None; state_count + 1];
+        let mut queue: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        costs[start] = 0;
+        queue.push(Reverse((0, start)));
+        let mut reached = None;
+        while let Some(Reverse((cost, state))) = queue.pop() {
+            if cost > costs[state] {
+                continue;
+            }
+            let node = heads[state];
+            if node == target {
+                reached = Some(state);
+                break;
+            }
+            for (position, arc) in self.adjacent_arcs[node].iter().enumerate() {
+                let is_u_turn = self._is_u_turn(tails[state], node, arc.index);
+                let arc_cost = if is_u_turn { arc.cost.saturating_add(penalty) } else { arc.cost };
+                let next = offsets[node] + position;
+                let new_cost = cost.saturating_add(arc_cost);
+                if new_cost < costs[next] {
+                    costs[next] = new_cost;
+                    predecessors[next] = Some(state);
+                    queue.push(Reverse((new_cost, next)));
+                }
+            }
+        }
+
+        let mut path = vec![target];
+        let mut state = reached?;
+        while let Some(previous) = predecessors[state] {
+            path.push(heads[previous]);
+            state = previous;
+        }
+        path.reverse();
+        Some((self._path_cost(&path), self._to_osm_ids(path)))
+    }
+
+    // Whether `tail -> node -> head` (indices) turns back. Without coordinates
+    // only going straight back to `tail` does.
+    fn _is_u_turn(&self, tail: usize, node: usize, head: usize) -> bool {
+        if head == tail {
+            return true;
+        }
+        let (Some(a), Some(b), Some(c)) = (self.point_by_index(tail), self.point_by_index(node), self.point_by_index(head)) else {
+            return false;
+        };
+        // between -180 and 180 degrees, 0 is straight on
+        let turn = (b.bearing(c) - a.bearing(b) + 540_f32) % 360_f32 - 180_f32;
+        turn.abs() > U_TURN_ANGLE
+    }
+
+    // Shortest paths to every node reachable from `source`, or only to
+    // those within `max_cost` seconds, which stops the search early.
+    pub fn shortest_path_tree(&self, source: isize, max_cost: Option<usize>) -> Option<ShortestPathTree<'_>> {
+        let index = self.get_index(source)?;
+        let search = self._dijkstra(index, None, None, max_cost, |_, _, arc| Some(arc.cost)).ok()?;
+        // `usize::MAX` marks unreached nodes, so it is never within the limit
+        let max_cost = max_cost.unwrap_or(usize::MAX).min(usize::MAX - 1);
+        Some(ShortestPathTree {network: self, source, search, max_cost})
+    }
+
+    // Chains the shortest paths between consecutive `stops`; the junction
+    // node shared by two legs appears only once in the returned path.
+    pub fn route_with_waypoints(&self, stops: &[isize]) -> Option<(usize, Vec<isize>)> {
+        let (&first, rest) = stops.split_first()?;
+        self.get_index(first)?;
+        let mut total_cost = 0;
+        let mut full_path = vec![first];
+        let mut previous = first;
+        for &stop in rest {
+            let (cost, path) = self.shortest_path(previous, stop)?;
+            total_cost = cost.saturating_add(total_cost);
+            full_path.extend_from_slice(&path[1..]);
+            previous = stop;
+        }
+        Some((total_cost, full_path))
+    }
+
+    // Up to `count` different routes between two nodes: after each route,
+    // the costs of its edges (in both directions) are multiplied by
+    // `penalty_factor` (> 1) and the search is repeated, which pushes later
+    // routes off the roads used before. Much cheaper than a k-shortest-paths
+    // algorithm, but the results are not the k shortest routes, and fewer
+    // than `count` are returned once re-routing keeps finding known routes.
+    // Costs in the returned routes are the unpenalized ones; the first route
+    // is the shortest path.
+    pub fn route_alternatives_via_penalty(
+        &self, from: isize, to: isize, count: usize, penalty_factor: f32
+    ) -> Vec<Route> {
+        let (Some(source), Some(target)) = (self.get_index(from), self.get_index(to)) else {
+            return Vec::new();
+        };
+        let mut penalties: HashMap<(usize, usize), f32> = HashMap::new();
+        let mut paths: Vec<Vec<usize>> = Vec::new();
+        // a repeated route still gets penalized, so give it a few more tries
+        for _ in 0..count.saturating_mul(2) {
+            if paths.len() == count {
+                break;
+            }
+            let search = self._dijkstra(source, Some(target), None, None, |tail, _, arc| {
+                let penalty = penalties.get(&(tail.min(arc.index), tail.max(arc.index))).copied().unwrap_or(1_f32);
+                Some(((arc.cost as f32 * penalty) as usize).min(MAX_ARC_COST))
+            });
+            let Some(path) = search.ok().and_then(|search| search.path_to(target)) else { break };
+            for pair in path.windows(2) {
+                *penalties.entry((pair[0].min(pair[1]), pair[0].max(pair[1]))).or_insert(1_f32) *= penalty_factor;
+            }
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths.into_iter().map(|path| {
+            let cost = self._path_cost(&path);
+            let path = self._to_osm_ids(path);
+            Route {cost, geometry: self._geometry(&path), path, snap_distances: (0_f32, 0_f32)}
+        }).collect()
+    }
+
+    // Sum of the cheapest arcs between consecutive nodes of `path` (indices).
+    fn _path_cost(&self, path: &[usize]) -> usize {
+        path.windows(2)
+            .filter_map(|pair| {
+                self.adjacent_arcs[pair[0]].iter().filter(|arc| arc.index == pair[1]).map(|arc| arc.cost).min()
+            })
+            .fold(0, usize::saturating_add)
+    }
+
+    // OSM IDs of the ways a path (OSM IDs) follows, in order and without
+    // repeating a way for consecutive arcs. Between two nodes the cheapest
+    // arc counts; arcs without a known way are skipped.
+    pub fn way_ids_along(&self, path: &[isize]) -> Vec<isize> {
+        let mut ways: Vec<isize> = Vec::new();
+        for pair in path.windows(2) {
+            let (Some(tail), Some(head)) = (self.get_index(pair[0]), self.get_index(pair[1])) else { continue };
+            let cheapest = self.adjacent_arcs[tail].iter().filter(|arc| arc.index == head).min_by_key(|arc| arc.cost);
+            if let Some(way) = cheapest.and_then(|arc| arc.way) {
+                if ways.last() != Some(&way) {
+                    ways.push(way);
+                }
+            }
+        }
+        ways
+    }
+
+    // Length in meters of a path (OSM IDs, e.g. `Route::path`), summed in
+    // `f64` so that long routes don't drift like a sum of thousands of `f32`
+    // segment lengths would. Segments with a node without coordinates count 0.
+    pub fn route_length_meters(&self, path: &[isize]) -> f64 {
+        path.windows(2)
+            .filter_map(|pair| Some((*self.nodes.get(&pair[0])?, *self.nodes.get(&pair[1])?)))
+            .map(|(a, b)| self.distance_model.distance(a, b) as f64)
+            .fold(0_f64, |total, meters| total + meters)  // `sum` starts at -0
+    }
+
+    // The fastest route with its length: one search for time, the distance
+    // is summed along the path found (as in `route_length_meters`).
+    pub fn route_cost_breakdown(&self, from: isize, to: isize) -> Option<CostBreakdown> {
+        let (cost, path) = self.shortest_path(from, to)?;
+        let meters = self.route_length_meters(&path);
+        let average_kmh = Some(cost).filter(|&cost| cost > 0).map(|cost| meters / cost as f64 * 3.6);
+        Some(CostBreakdown {cost, meters, average_kmh, path})
+    }
+
+    // Coordinates of the nodes in `ids` (e.g. a path from `shortest_path`).
+    // Unlike `Route::geometry`, which leaves out nodes without coordinates,
+    // fails with `OsmError::MissingNode` on the first of those.
+    pub fn ids_to_coords(&self, ids: &[isize]) -> Result<Vec<Point>, OsmError> {
+        ids.iter().map(|osm_id| self.nodes.get(osm_id).copied().ok_or(OsmError::MissingNode(*osm_id))).collect()
+    }
+
+    pub(crate) fn _geometry(&self, path: &[isize]) -> Vec<Point> {
+        path.iter().filter_map(|osm_id| self.nodes.get(osm_id).copied()).collect()
+    }
+
+    // Where `location` is relative to a route (OSM IDs as returned by
+    // `shortest_path`): the position in `route` of the nearest node and the
+    // distance in meters to the route itself. The location is projected onto
+    // every segment, so a point beside a long segment is close to the route
+    // even if both of its nodes are far away; the index is that of the
+    // segment end nearer to the projection. `(0, f32::INFINITY)` if no node of
+    // the route has coordinates.
+    pub fn closest_node_on_route(&self, route: &[isize], location: Point) -> (usize, f32) {
+        let points: Vec<(usize, Point)> = route.iter().enumerate()
+            .filter_map(|(position, osm_id)| self.nodes.get(osm_id).map(|&point| (position, point)))
+            .collect();
+        let mut closest = match points.first() {
+            Some(&(position, point)) => (position, self.distance_model.distance(point, location)),
+            None => return (0, f32::INFINITY),
+        };
+        for pair in points.windows(2) {
+            let ((position_a, a), (position_b, b)) = (pair[0], pair[1]);
+            let t = projection(a, b, location);
+            let distance = self.distance_model.distance(a.interpolate(b, t), location);
+            if distance < closest.1 {
+                closest = (if t < 0.5_f32 { position_a } else { position_b }, distance);
+            }
+        }
+        closest
+    }
+
+    // Snaps both coordinates to the closest node of the road graph (within
+    // `DEFAULT_SNAP_RADIUS`) and routes between them.
+    pub fn snap_and_route(&self, from: Point, to: Point) -> Option<Route> {
+        self.snap_and_route_with(from, to, SnapPolicy::default())
+    }
+
+    pub fn snap_and_route_with(&self, from: Point, to: Point, policy: SnapPolicy) -> Option<Route> {
+        let (from, to) = (self.snap_detailed(from, policy)?, self.snap_detailed(to, policy)?);
+        let (cost, path) = self.shortest_path(from.osm_id, to.osm_id)?;
+        Some(Route {cost, geometry: self._geometry(&path), path, snap_distances: (from.distance, to.distance)})
+    }
+
+    // Routes between two coordinates, each snapped to a nearby routable node
+    // however far away it is; check `Route::snap_distances` to detect a
+    // point far off the network. The snapped nodes are the first and last
+    // of `Route::path`. If the closest nodes aren't connected (e.g. one lies
+    // on a small disconnected fragment), the `SNAP_CANDIDATES` closest ones
+    // of each point are paired up and tried by their total snap distance.
+    pub fn route_between_points(&self, from: Point, to: Point) -> Option<Route> {
+        let from_candidates = self._nearest_routable(from, SNAP_CANDIDATES);
+        let to_candidates = self._nearest_routable(to, SNAP_CANDIDATES);
+        let mut pairs: Vec<(usize, usize)> = (0..from_candidates.len())
+            .flat_map(|from_position| (0..to_candidates.len()).map(move |to_position| (from_position, to_position)))
+            .collect();
+        let snap_distance = |&(from_position, to_position): &(usize, usize)| {
+            from_candidates[from_position].1 + to_candidates[to_position].1
+        };
+        pairs.sort_by(|a, b| snap_distance(a).total_cmp(&snap_distance(b)));
+        // one search per start candidate, run once it's needed
+        let mut trees: Vec<Option<ShortestPathTree>> = from_candidates.iter().map(|_| None).collect();
+        for (from_position, to_position) in pairs {
+            let ((from_id, from_distance), (to_id, to_distance)) = (from_candidates[from_position], to_candidates[to_position]);
+            if trees[from_position].is_none() {
+                trees[from_position] = self.shortest_path_tree(from_id, None);
+            }
+            if let Some((cost, path)) = trees[from_position].as_ref()?.path_to(to_id) {
+                return Some(Route {
+                    cost, geometry: self._geometry(&path), path, snap_distances: (from_distance, to_distance)
+                });
+            }
+        }
+        None
+    }
+
+    // Up to `count` routable nodes closest to `location` with their
+    // distances, nearest first (ties go to the lower OSM ID).
+    fn _nearest_routable(&self, location: Point, count: usize) -> Vec<(isize, f32)> {
+        self._nearest(location, count, |entry| entry.routable)
+    }
+
+    // Closest routable node (one with an arc) according to `policy`.
+    pub fn snap(&self, location: Point, policy: SnapPolicy) -> Option<isize> {
+        self.snap_detailed(location, policy).map(|snap| snap.osm_id)
+    }
+
+    // Like `snap`, but also tells how far away the node is and whether
+    // `WithinThenNearest` had to fall back to a node outside its radius.
+    pub fn snap_detailed(&self, location: Point, policy: SnapPolicy) -> Option<Snap> {
+        let (osm_id, distance) = self._nearest(location, 1, |entry| entry.routable).pop()?;
+        let fallback = match policy {
+            SnapPolicy::StrictWithin(max_meters) if distance > max_meters => return None,
+            SnapPolicy::WithinThenNearest(max_meters) => distance > max_meters,
+            _ => false,
+        };
+        Some(Snap {osm_id, distance, fallback})
+    }
+}
+
+// Position (0 at `a`, 1 at `b`) of the point on segment a-b closest to
+// `location`, in a local flat approximation.
+pub(crate) fn projection(a: Point, b: Point, location: Point) -> f32 {
+    let lon_scale = location.lat.to_radians().cos();
+    let (dx, dy) = ((b.lon - a.lon) * lon_scale, b.lat - a.lat);
+    let (px, py) = ((location.lon - a.lon) * lon_scale, location.lat - a.lat);
+    let length = dx * dx + dy * dy;
+    if length == 0_f32 {
+        return 0_f32;
+    }
+    ((px * dx + py * dy) / length).clamp(0_f32, 1_f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{fixtures, SpeedProfile};
+
+    #[test]
+    fn settled_node_limit() {
+        let network = fixtures::grid(10);
+        assert_eq!(network.shortest_path_limited(1, 100, Some(5)), Err(RoutingError::LimitExceeded));
+        let (cost, path) = network.shortest_path_limited(1, 100, Some(1000)).unwrap();
+        assert_eq!(Some((cost, path)), network.shortest_path(1, 100));
+        assert_eq!(network.shortest_path_limited(1, 0, None), Err(RoutingError::UnknownNode(0)));
+    }
+
+    #[test]
+    fn route_with_waypoints_chains_legs() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, true), (3, 4, true), (5, 6, true)]);
+        let leg_cost = |from, to| network.shortest_path(from, to).unwrap().0;
+        let (cost, path) = network.route_with_waypoints(&[1, 4, 2]).unwrap();
+        assert_eq!(path, vec![1, 2, 3, 4, 3, 2]);
+        assert_eq!(cost, leg_cost(1, 4) + leg_cost(4, 2));
+        assert_eq!(network.route_with_waypoints(&[1, 4, 5]), None);
+        assert_eq!(network.route_with_waypoints(&[]), None);
+    }
+
+    #[test]
+    fn shortest_path_tree_matches_single_queries() {
+        let network = fixtures::grid(5);
+        let tree = network.shortest_path_tree(7, None).unwrap();
+        assert_eq!(tree.source(), 7);
+        for target in [1, 7, 13, 19, 25] {
+            assert_eq!(tree.path_to(target), network.shortest_path(7, target));
+        }
+        assert_eq!(tree.path_to(26), None);
+        assert_eq!(network.shortest_path_tree(26, None).map(|tree| tree.source()), None);
+    }
+
+    #[test]
+    fn shortest_path_tree_within_max_cost() {
+        let network = fixtures::grid(5);
+        let one_hop = network.shortest_path(1, 2).unwrap().0;
+        let tree = network.shortest_path_tree(1, Some(one_hop)).unwrap();
+        assert_eq!(tree.cost_to(2), Some(one_hop));
+        assert_eq!(tree.cost_to(3), None);
+        assert_eq!(tree.path_to(25), None);
+    }
+
+    #[test]
+    fn filtered_arcs_force_a_detour() {
+        let network = fixtures::grid(3);
+        let (cost, path) = network.shortest_path(1, 3).unwrap();
+        assert_eq!(path, vec![1, 2, 3]);
+        // close every arc into node 2
+        let closed = network.get_index(2).unwrap();
+        let (detour_cost, detour) = network.shortest_path_filtered(1, 3, |arc| arc.index != closed).unwrap();
+        assert!(!detour.contains(&2));
+        assert_eq!((detour[0], detour[detour.len() - 1]), (1, 3));
+        assert!(detour.windows(2).all(|pair| fixtures::has_arc(&network, pair[0], pair[1])));
+        assert!(detour_cost > cost);
+        assert_eq!(network.shortest_path_filtered(1, 3, |_| false), None);
+        // the network itself is untouched
+        assert_eq!(network.shortest_path(1, 3), Some((cost, path)));
+    }
+
+    #[test]
+    fn snap_and_route_between_coordinates() {
+        let network = fixtures::grid(3);
+        // about 20 m south of node 1 and 20 m north of node 9
+        let (from, to) = (Point {lat: 48.99982, lon: 7_f32}, Point {lat: 49.00218, lon: 7.002});
+        let route = network.snap_and_route(from, to).unwrap();
+        let (cost, path) = network.shortest_path(1, 9).unwrap();
+        assert_eq!((route.cost, &route.path), (cost, &path));
+        assert_eq!(route.geometry.len(), path.len());
+        assert!((route.snap_distances.0 - 20_f32).abs() < 1_f32 && (route.snap_distances.1 - 20_f32).abs() < 1_f32);
+        let far = Point {lat: 49.1, lon: 7_f32};
+        assert_eq!(network.snap_and_route(from, far), None);
+        assert_eq!(network.snap_and_route(far, to), None);
+    }
+
+    #[test]
+    fn snap_policies_far_from_the_network() {
+        let network = fixtures::grid(3);
+        // about 1.1 km north of node 7
+        let far = Point {lat: 49.012, lon: 7_f32};
+        assert_eq!(network.snap(far, SnapPolicy::default()), None);
+        assert_eq!(network.snap(far, SnapPolicy::StrictWithin(2000_f32)), Some(7));
+        let nearest = network.snap_detailed(far, SnapPolicy::NearestAlways).unwrap();
+        assert_eq!((nearest.osm_id, nearest.fallback), (7, false));
+        assert!((nearest.distance - 1112_f32).abs() < 5_f32);
+        let fallback = network.snap_detailed(far, SnapPolicy::WithinThenNearest(500_f32)).unwrap();
+        assert_eq!(fallback, Snap {fallback: true, ..nearest});
+        let within = network.snap_detailed(far, SnapPolicy::WithinThenNearest(2000_f32)).unwrap();
+        assert_eq!(within, Snap {fallback: false, ..nearest});
+        assert_eq!(network.snap_and_route_with(far, Point {lat: 49_f32, lon: 7_f32}, SnapPolicy::default()), None);
+        let route = network.snap_and_route_with(far, Point {lat: 49_f32, lon: 7_f32}, SnapPolicy::WithinThenNearest(500_f32));
+        assert_eq!(route.map(|route| route.path), Some(vec![7, 4, 1]));
+    }
+
+    #[test]
+    fn snapping_skips_nodes_left_without_arcs() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49.0001, 7.0015), (4, 49.0001, 7.0025)],
+            &[(10, &[1, 2], &[("highway", "residential")]), (11, &[3, 4], &[("highway", "motorway")])],
+        );
+        let mut network = fixtures::parse(&xml);
+        // no cycling on the motorway, but 3 and 4 keep their indices
+        network.recompute_costs(&SpeedProfile::bicycle());
+        assert!(network.get_index(3).is_some() && !fixtures::has_arc(&network, 3, 4));
+        let query = Point {lat: 49.0001, lon: 7.0014};
+        assert_eq!(network.snap(query, SnapPolicy::default()), Some(2));
+        let route = network.snap_and_route(query, Point {lat: 49_f32, lon: 7_f32});
+        assert_eq!(route.map(|route| route.path), Some(vec![2, 1]));
+    }
+
+    #[test]
+    fn closest_node_on_route_projects_onto_segments() {
+        // a short segment 1-2 and a long one 2-10, about 585 m
+        let network = fixtures::network(&[(1, 2, true), (2, 10, true)]);
+        let route = [1, 2, 10];
+        // about 56 m north of the long segment, nearer to its end
+        let (position, distance) = network.closest_node_on_route(&route, Point {lat: 49.0005, lon: 7.007});
+        assert_eq!(position, 2);
+        assert!((distance - 55.6).abs() < 0.5, "{}", distance);
+        // same offset, nearer to its start
+        assert_eq!(network.closest_node_on_route(&route, Point {lat: 49.0005, lon: 7.0045}).0, 1);
+        // before the start of the route
+        let (position, distance) = network.closest_node_on_route(&route, Point {lat: 49_f32, lon: 7_f32});
+        assert_eq!(position, 0);
+        assert!((distance - 73_f32).abs() < 1_f32, "{}", distance);
+        assert_eq!(network.closest_node_on_route(&[], Point {lat: 49_f32, lon: 7_f32}), (0, f32::INFINITY));
+    }
+
+    #[test]
+    fn penalized_alternatives_are_distinct() {
+        let network = fixtures::grid(4);
+        let edges = |route: &Route| -> HashSet<(isize, isize)> {
+            route.path.windows(2).map(|pair| (pair[0].min(pair[1]), pair[0].max(pair[1]))).collect()
+        };
+        // without a penalty, re-routing finds the same route over and over
+        assert_eq!(network.route_alternatives_via_penalty(1, 16, 3, 1_f32).len(), 1);
+        let routes = network.route_alternatives_via_penalty(1, 16, 3, 2_f32);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(Some(routes[0].cost), network.shortest_path(1, 16).map(|(cost, _)| cost));
+        for route in &routes {
+            assert_eq!((route.path[0], route.path[route.path.len() - 1]), (1, 16));
+            assert_eq!(fixtures::path_cost(&network, &route.path), Some(route.cost));
+            assert!(route.cost >= routes[0].cost);
+        }
+        let first = edges(&routes[0]);
+        for route in &routes[1..] {
+            assert!(edges(route).intersection(&first).count() < first.len());
+            assert_ne!(route.path, routes[0].path);
+        }
+        assert_ne!(routes[1].path, routes[2].path);
+        assert!(network.route_alternatives_via_penalty(1, 100, 3, 2_f32).is_empty());
+    }
+
+    #[test]
+    fn long_chain_length_matches_the_straight_line() {
+        // 20000 segments of about 5.6 m along a meridian
+        let count = 20_000;
+        let mut network: RoadNetwork = (0..=count)
+            .map(|step| (step as isize, Point {lat: 40_f32 + step as f32 * 0.00005, lon: 7_f32}))
+            .collect();
+        network.add_arcs((0..count).map(|step| (step as isize, step as isize + 1, 10_f32)));
+        let (cost, path) = network.shortest_path(0, count as isize).unwrap();
+        assert_eq!(path.len(), count + 1);
+        // truncated once per arc, summed exactly
+        assert_eq!(Some(cost), fixtures::path_cost(&network, &path));
+        let straight = network.distance(0, count as isize) as f64;
+        let length = network.route_length_meters(&path);
+        assert!((length - straight).abs() / straight < 1e-3, "{} {}", length, straight);
+        assert_eq!(network.route_length_meters(&[0]), 0_f64);
+    }
+
+    #[test]
+    fn display_routes() {
+        let route = Route {cost: 34, path: vec![1, 7, 2, 9, 3], geometry: Vec::new(), snap_distances: (0_f32, 0_f32)};
+        assert_eq!(route.to_string(), "34s over 5 nodes from 1 to 3");
+        assert_eq!(Route {path: Vec::new(), ..route}.to_string(), "34s over 0 nodes");
+    }
+
+    #[test]
+    fn ids_to_coords_of_a_path() {
+        let network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        let (_, path) = network.shortest_path(3, 1).unwrap();
+        let coords = network.ids_to_coords(&path).unwrap();
+        assert_eq!(coords, vec![Point {lat: 49_f32, lon: 7.003}, Point {lat: 49_f32, lon: 7.002}, Point {lat: 49_f32, lon: 7.001}]);
+        assert_eq!(network.ids_to_coords(&[]), Ok(Vec::new()));
+        assert_eq!(network.ids_to_coords(&[1, 4, 5]), Err(OsmError::MissingNode(4)));
+    }
+
+    #[test]
+    fn cost_breakdown_sums_the_arc_lengths() {
+        // the direct road 1-3 is slow, the detour over 2 fast
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        network.add_node(4, Point {lat: 49.0005, lon: 7.002});
+        network.add_arc(1, 4, 10_f32);
+        network.add_arc(4, 3, 1_f32);
+        let breakdown = network.route_cost_breakdown(1, 3).unwrap();
+        assert_eq!(breakdown.path, vec![1, 2, 3]);
+        assert_eq!(Some(breakdown.cost), fixtures::path_cost(&network, &breakdown.path));
+        let lengths: f64 = breakdown.path.windows(2)
+            .map(|pair| {
+                let (tail, head) = (network.get_index(pair[0]).unwrap(), network.get_index(pair[1]).unwrap());
+                network.adjacent_arcs()[tail].iter().find(|arc| arc.index == head).unwrap().length.unwrap() as f64
+            })
+            .sum();
+        assert!((breakdown.meters - lengths).abs() < 1e-3, "{} {}", breakdown.meters, lengths);
+        let kmh = breakdown.average_kmh.unwrap();
+        assert!((kmh - lengths / breakdown.cost as f64 * 3.6).abs() < 1e-6);
+        // costs are truncated per arc, so the average is a bit above 36 km/h
+        assert!((36_f64..40_f64).contains(&kmh), "{}", kmh);
+        let breakdown = network.route_cost_breakdown(2, 2).unwrap();
+        assert_eq!((breakdown.cost, breakdown.meters, breakdown.average_kmh), (0, 0_f64, None));
+        assert_eq!(network.route_cost_breakdown(1, 5), None);
+    }
+
+    #[test]
+    fn u_turn_penalty_prefers_going_around_the_block() {
+        // a block 1-2-3-4-5 with the vehicle at 2, having come from 1
+        let mut network: RoadNetwork = [(1, 0_f32, 0_f32), (2, 0_f32, 0.001), (3, 0_f32, 0.002), (4, 0.001, 0.002), (5, 0.001, 0_f32)]
+            .iter()
+            .map(|&(osm_id, lat, lon)| (osm_id, Point {lat: 49_f32 + lat, lon: 7_f32 + lon}))
+            .collect();
+        network.add_arcs([(1, 2, 10_f32), (2, 3, 10_f32), (3, 4, 10_f32), (4, 5, 10_f32), (5, 1, 10_f32)]);
+        let back = network.shortest_path(2, 1).unwrap();
+        assert_eq!(back.1, vec![2, 1]);
+        assert_eq!(network.route_with_u_turn_penalty(2, 1, None, 1000), Some(back.clone()));
+        assert_eq!(network.route_with_u_turn_penalty(2, 1, Some(1), 0), Some(back.clone()));
+        assert_eq!(network.route_with_u_turn_penalty(2, 1, Some(3), 1000), Some(back.clone()));
+        let (cost, path) = network.route_with_u_turn_penalty(2, 1, Some(1), 1000).unwrap();
+        assert_eq!(path, vec![2, 3, 4, 5, 1]);
+        // the penalty isn't part of the cost
+        assert_eq!(Some(cost), fixtures::path_cost(&network, &path));
+        assert!(cost > back.0);
+        // a small penalty isn't worth the detour
+        assert_eq!(network.route_with_u_turn_penalty(2, 1, Some(1), 5), Some(back));
+        // U-turns further along the route are penalized as well
+        network.add_node(6, Point {lat: 49_f32, lon: 7.003});
+        network.add_arc(3, 6, 10_f32);
+        assert_eq!(network.route_with_u_turn_penalty(2, 6, Some(1), 1000).map(|(_, path)| path), Some(vec![2, 3, 6]));
+        assert_eq!(network.route_with_u_turn_penalty(2, 7, Some(1), 1000), None);
+        // swerving sharply back into a parallel lane is turning around as well
+        network.add_node(8, Point {lat: 49.00005, lon: 7.0001});
+        network.add_arcs([(2, 8, 10_f32), (8, 1, 10_f32)]);
+        assert_eq!(network.route_with_u_turn_penalty(2, 1, Some(1), 1000).map(|(_, path)| path), Some(vec![2, 3, 4, 5, 1]));
+        assert_eq!(network.route_with_u_turn_penalty(2, 8, Some(3), 1000).map(|(_, path)| path), Some(vec![2, 8]));
+    }
+
+    #[test]
+    fn tree_edges_as_geojson() {
+        let mut network = fixtures::grid(6);
+        // a few faster roads, so the tree isn't the same in every direction
+        network.add_arcs([(1, 8, 30_f32), (8, 15, 30_f32), (15, 22, 30_f32), (22, 29, 30_f32)]);
+        let max_cost = network.shortest_path(1, 36).unwrap().0 / 2;
+        let tree = network.shortest_path_tree(1, Some(max_cost)).unwrap();
+        let reached: Vec<(isize, usize, Option<isize>)> = tree.reached().collect();
+        assert!(reached.len() > 1 && reached.len() < 36);
+        let mut state: u32 = 99;
+        for _ in 0..50 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let target = (state >> 16) as isize % 37 + 1;
+            match (tree.path_to(target), network.shortest_path(1, target)) {
+                (Some((cost, path)), Some((expected, _))) => {
+                    assert_eq!(cost, expected);
+                    assert_eq!(fixtures::path_cost(&network, &path), Some(cost));
+                    assert!(reached.iter().any(|&(osm_id, reached_cost, _)| (osm_id, reached_cost) == (target, cost)));
+                }
+                (None, Some((expected, _))) => assert!(expected > max_cost),
+                (tree_path, expected) => assert_eq!(tree_path, expected),
+            }
+        }
+        for &(osm_id, cost, predecessor) in &reached {
+            assert!(cost <= max_cost);
+            match predecessor {
+                Some(predecessor) => assert_eq!(tree.cost_to(predecessor).unwrap() + fixtures::path_cost(&network, &[predecessor, osm_id]).unwrap(), cost),
+                None => assert_eq!((osm_id, cost), (1, 0)),
+            }
+        }
+        let mut geojson = Vec::new();
+        tree.write_geojson(&mut geojson).unwrap();
+        let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), reached.len() - 1);
+        for feature in features {
+            let properties = &feature["properties"];
+            let to = properties["to"].as_i64().unwrap() as isize;
+            assert_eq!(properties["cost"].as_u64().map(|cost| cost as usize), tree.cost_to(to));
+            let coordinates = &feature["geometry"]["coordinates"];
+            let point = network.nodes[&to];
+            let (lon, lat) = (coordinates[1][0].as_f64().unwrap() as f32, coordinates[1][1].as_f64().unwrap() as f32);
+            assert_eq!(Point {lat, lon}, point);
+        }
+    }
+
+    #[test]
+    fn points_snap_near_a_street() {
+        // a street 1-2-3-4 along 49°N and a disconnected stub 10-11 just
+        // north of node 1
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true), (3, 4, true)]);
+        network.add_nodes([(10, Point {lat: 49.0002, lon: 7.001}), (11, Point {lat: 49.0003, lon: 7.001})]);
+        network.add_arc(10, 11, 10_f32);
+        let from = Point {lat: 49.0001, lon: 7.0021};
+        let to = Point {lat: 48.9999, lon: 7.004};
+        let route = network.route_between_points(from, to).unwrap();
+        assert_eq!(route.path, vec![2, 3, 4]);
+        assert_eq!(Some((route.cost, route.path.clone())), network.shortest_path(2, 4));
+        assert!((route.snap_distances.0 - network.distance_model.distance(from, network.nodes[&2])).abs() < 1e-3);
+        assert!((12_f32..15_f32).contains(&route.snap_distances.0), "{:?}", route.snap_distances);
+        assert!((10_f32..12_f32).contains(&route.snap_distances.1), "{:?}", route.snap_distances);
+        // 10 is closest to this point, but can't reach the street
+        let near_stub = Point {lat: 49.00021, lon: 7.001};
+        assert_eq!(network._nearest_routable(near_stub, 1)[0].0, 10);
+        let route = network.route_between_points(near_stub, to).unwrap();
+        assert_eq!(route.path, vec![1, 2, 3, 4]);
+        assert!(route.snap_distances.0 > 20_f32);
+        // a point kilometers away still snaps, with a telling distance
+        let far = network.route_between_points(Point {lat: 49.1, lon: 7.002}, to).unwrap();
+        assert!(far.snap_distances.0 > 10_000_f32);
+        assert_eq!(RoadNetwork::new().route_between_points(from, to), None);
+    }
+}
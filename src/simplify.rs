@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use crate::routing::projection;
+use crate::{Arc, Point, RoadNetwork, MAX_ARC_COST};
+
+// Size of the network before and after one step of `simplify_to_routing_core`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StageReport {
+    pub stage: &'static str,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    pub arcs_before: usize,
+    pub arcs_after: usize,
+}
+
+impl RoadNetwork {
+    // The usual cleanup before routing, in an order where each step helps the
+    // next: keep the largest strongly connected component, drop parallel arcs
+    // but the cheapest, contract chains of shape nodes (see
+    // `simplify_geometry`, here without any tolerance) and remove nodes left
+    // without arcs. Routes between the remaining nodes cost the same as
+    // before.
+    pub fn simplify_to_routing_core(&mut self) -> Vec<StageReport> {
+        let parse_stats = self.parse_stats;
+        let reports = vec![
+            self._stage("largest component", |network| *network = network.largest_strongly_connected()),
+            self._stage("parallel arcs", RoadNetwork::_dedup_arcs),
+            self._stage("shape nodes", |network| network.simplify_geometry(f32::INFINITY)),
+            self._stage("isolated nodes", |network| {
+                let mut keep: Vec<bool> = network.adjacent_arcs.iter().map(|arcs| !arcs.is_empty()).collect();
+                for arc in network.adjacent_arcs.iter().flatten() {
+                    keep[arc.index] = true;
+                }
+                *network = network._subgraph(&keep);
+            }),
+        ];
+        self.parse_stats = parse_stats;
+        reports
+    }
+
+    fn _stage(&mut self, stage: &'static str, run: impl FnOnce(&mut RoadNetwork)) -> StageReport {
+        let (nodes_before, arcs_before) = (self.node_count(), self.arc_count());
+        run(self);
+        StageReport {stage, nodes_before, nodes_after: self.node_count(), arcs_before, arcs_after: self.arc_count()}
+    }
+
+    // Keeps only the cheapest of several arcs with the same tail and head.
+    fn _dedup_arcs(&mut self) {
+        for arcs in &mut self.adjacent_arcs {
+            arcs.sort_by_key(|arc| (arc.index, arc.cost));
+            arcs.dedup_by_key(|arc| arc.index);
+        }
+    }
+
+    // Douglas–Peucker on the shape of the roads: chains of shape nodes (two
+    // neighbors, both arcs from the same way) between junctions are thinned
+    // out so that no dropped node is more than `tolerance_meters` away from
+    // the remaining polyline. Junctions, barriers, nodes with conditional
+    // costs, ends of arcs with dimension limits and nodes without
+    // coordinates always stay. The arcs replacing a
+    // dropped stretch cost the sum of the original arcs, so routes keep
+    // their costs.
+    pub fn simplify_geometry(&mut self, tolerance_meters: f32) {
+        let shape = self._shape_nodes();
+        let mut keep = vec![true; self.adjacent_arcs.len()];
+        let mut visited = vec![false; self.adjacent_arcs.len()];
+        for start in 0..self.adjacent_arcs.len() {
+            if shape[start] {
+                continue;
+            }
+            for arc in &self.adjacent_arcs[start] {
+                if !shape[arc.index] || visited[arc.index] {
+                    continue;
+                }
+                let chain = self._follow_chain(start, arc.index, &shape);
+                for &index in &chain[1..chain.len() - 1] {
+                    visited[index] = true;
+                }
+                let points: Option<Vec<Point>> = chain.iter()
+                    .map(|index| self.point_by_index(*index))
+                    .collect();
+                let Some(points) = points else { continue };
+                let closed = start == chain[chain.len() - 1];
+                for (position, retained) in self._douglas_peucker(&points, tolerance_meters, closed).into_iter().enumerate() {
+                    keep[chain[position]] = retained;
+                }
+            }
+        }
+        let parse_stats = self.parse_stats;
+        let mut simplified = self._spliced(&keep);
+        simplified.parse_stats = parse_stats;
+        *self = simplified;
+    }
+
+    // Nodes that only continue a single way: either both neighbors are
+    // connected in both directions or the node is passed in one direction.
+    fn _shape_nodes(&self) -> Vec<bool> {
+        let mut incoming: Vec<Vec<&Arc>> = vec![Vec::new(); self.adjacent_arcs.len()];
+        let mut tails: Vec<Vec<usize>> = vec![Vec::new(); self.adjacent_arcs.len()];
+        // splicing keeps the way of the first arc, so limited arcs stay whole
+        let limited = |way| self.dimension_limits.contains_key(&way) || self.conditional_speeds.contains_key(&way);
+        let mut pinned: HashSet<usize> = HashSet::new();
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                incoming[arc.index].push(arc);
+                tails[arc.index].push(index);
+                if arc.way.is_some_and(limited) {
+                    pinned.extend([index, arc.index]);
+                }
+            }
+        }
+        (0..self.adjacent_arcs.len()).map(|index| {
+            let osm_id = self.osm_ids[index];
+            if pinned.contains(&index) || self.barriers.contains_key(&osm_id) || !self.nodes.contains_key(&osm_id) {
+                return false;
+            }
+            let outgoing = &self.adjacent_arcs[index];
+            // the ends of one-way roads only have arcs in one direction
+            if outgoing.is_empty() || incoming[index].is_empty() {
+                return false;
+            }
+            let same_way = outgoing.iter().chain(incoming[index].iter().copied())
+                .all(|arc| (arc.name, arc.class, arc.way) == (outgoing[0].name, outgoing[0].class, outgoing[0].way));
+            let mut heads: Vec<usize> = outgoing.iter().map(|arc| arc.index).collect();
+            let mut tails = tails[index].clone();
+            heads.sort_unstable();
+            tails.sort_unstable();
+            let through = match (heads.as_slice(), tails.as_slice()) {
+                ([a, b], _) if a == b || *a == index || *b == index => false,
+                ([_, _], _) => heads == tails,
+                ([head], [tail]) => head != tail && *head != index && *tail != index,
+                _ => false,
+            };
+            through && same_way
+        }).collect()
+    }
+
+    // Indices from `start` over the arc to `first` through shape nodes up
+    // to the next node that is not one.
+    fn _follow_chain(&self, start: usize, first: usize, shape: &[bool]) -> Vec<usize> {
+        let mut chain = vec![start];
+        let (mut previous, mut current) = (start, first);
+        loop {
+            chain.push(current);
+            if !shape[current] {
+                return chain;
+            }
+            let next = self._continue_from(previous, current);
+            previous = current;
+            current = next.index;
+        }
+    }
+
+    // The arc leaving a shape node `current` that doesn't lead back to `previous`.
+    fn _continue_from(&self, previous: usize, current: usize) -> &Arc {
+        let arcs = &self.adjacent_arcs[current];
+        arcs.iter().find(|arc| arc.index != previous).unwrap_or(&arcs[0])
+    }
+
+    // Which points to retain, always including both ends. A closed chain
+    // (first and last point are the same node) keeps its farthest point so
+    // it doesn't collapse into a self-loop.
+    fn _douglas_peucker(&self, points: &[Point], tolerance_meters: f32, closed: bool) -> Vec<bool> {
+        let last = points.len() - 1;
+        let mut retained = vec![false; points.len()];
+        retained[0] = true;
+        retained[last] = true;
+        let mut ranges = vec![(0, last)];
+        while let Some((first, last)) = ranges.pop() {
+            let (a, b) = (points[first], points[last]);
+            let farthest = (first + 1..last)
+                .map(|position| {
+                    let point = points[position];
+                    (position, self.distance_model.distance(a.interpolate(b, projection(a, b, point)), point))
+                })
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+            if let Some((position, distance)) = farthest {
+                if distance > tolerance_meters || closed && (first, last) == (0, points.len() - 1) {
+                    retained[position] = true;
+                    ranges.push((first, position));
+                    ranges.push((position, last));
+                }
+            }
+        }
+        retained
+    }
+
+    // Copy of the network without the nodes that aren't kept; arcs running
+    // through them are joined into one arc per stretch.
+    fn _spliced(&self, keep: &[bool]) -> RoadNetwork {
+        let mut spliced = self._empty_like();
+        for (index, osm_id) in self.osm_ids.iter().enumerate() {
+            if keep[index] {
+                spliced.get_or_create_index(*osm_id);
+                if let Some(&location) = self.nodes.get(osm_id) {
+                    spliced.add_node(*osm_id, location);
+                }
+                if let Some(barrier) = self.barriers.get(osm_id) {
+                    spliced.barriers.insert(*osm_id, barrier.clone());
+                }
+            }
+        }
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            if !keep[index] {
+                continue;
+            }
+            let new_index = spliced.osm_id_map[&self.osm_ids[index]];
+            for arc in arcs {
+                let (mut previous, mut current, mut cost, mut length) = (index, arc.index, arc.cost, arc.length);
+                while !keep[current] {
+                    let next = self._continue_from(previous, current);
+                    cost = cost.saturating_add(next.cost).min(MAX_ARC_COST);
+                    length = length.zip(next.length).map(|(length, next)| length + next);
+                    previous = current;
+                    current = next.index;
+                }
+                let head = spliced.osm_id_map[&self.osm_ids[current]];
+                spliced.adjacent_arcs[new_index].push(Arc {index: head, cost, length, ..*arc});
+            }
+        }
+        spliced.conditional_speeds = self.conditional_speeds.clone();
+        spliced.dimension_limits = self.dimension_limits.clone();
+        spliced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    #[test]
+    fn noisy_polyline_keeps_its_ends_and_junctions() {
+        // nodes 1 to 9 zigzag about 1 m around a straight line, node 5 also
+        // leads to node 20
+        let nodes: Vec<(isize, f32, f32)> = (1..=9)
+            .map(|osm_id| (osm_id, 49_f32 + if osm_id % 2 == 0 { 0.00001 } else { -0.00001 }, 7_f32 + osm_id as f32 * 0.001))
+            .chain([(20, 49.001, 7.005)])
+            .collect();
+        let residential: &[(&str, &str)] = &[("highway", "residential")];
+        let ways: [fixtures::Way; 2] = [(10, &[1, 2, 3, 4, 5, 6, 7, 8, 9], residential), (11, &[5, 20], residential)];
+        let mut network = fixtures::parse(&fixtures::osm_xml(&nodes, &ways));
+        let cost = network.shortest_path(1, 9).map(|(cost, _)| cost);
+        assert_eq!(network.node_count(), 10);
+        network.simplify_geometry(5_f32);
+        assert_eq!(network.node_count(), 4);
+        assert_eq!(network.shortest_path(1, 9), cost.map(|cost| (cost, vec![1, 5, 9])));
+        assert!(fixtures::has_arc(&network, 5, 20) && fixtures::has_arc(&network, 20, 5));
+    }
+
+    #[test]
+    fn one_way_chain_contracts_to_its_ends() {
+        let mut network = fixtures::network(&[(1, 2, false), (2, 3, false), (3, 4, false)]);
+        let cost = network.shortest_path(1, 4).map(|(cost, _)| cost);
+        network.simplify_geometry(f32::INFINITY);
+        assert_eq!((network.node_count(), network.arc_count()), (2, 1));
+        assert_eq!(network.shortest_path(1, 4), cost.map(|cost| (cost, vec![1, 4])));
+        assert_eq!(network.shortest_path(4, 1), None);
+    }
+
+    #[test]
+    fn routing_core_keeps_the_costs_between_junctions() {
+        // a ring through the junctions 1, 4 and 7 with two shape nodes between
+        // each, all three junctions linked to 10, a parallel arc 1-2 and a
+        // separate road 20-21
+        let mut network = fixtures::network(&[
+            (1, 2, true), (1, 2, true), (2, 3, true), (3, 4, true), (4, 5, true), (5, 6, true), (6, 7, true),
+            (7, 8, true), (8, 9, true), (9, 1, true), (1, 10, true), (4, 10, true), (7, 10, true), (20, 21, true),
+        ]);
+        let junctions = [1, 4, 7, 10];
+        let costs: Vec<Option<usize>> = junctions.iter()
+            .flat_map(|&from| junctions.iter().map(move |&to| (from, to)))
+            .map(|(from, to)| network.shortest_path(from, to).map(|(cost, _)| cost))
+            .collect();
+        let reports = network.simplify_to_routing_core();
+        let stages: Vec<&str> = reports.iter().map(|report| report.stage).collect();
+        assert_eq!(stages, ["largest component", "parallel arcs", "shape nodes", "isolated nodes"]);
+        assert_eq!((reports[0].nodes_before, reports[3].nodes_after), (12, 4));
+        assert_eq!(reports[1].arcs_before - reports[1].arcs_after, 2);
+        assert!(reports.windows(2).all(|pair| pair[0].nodes_after == pair[1].nodes_before));
+        assert_eq!(network.node_count(), 4);
+        let simplified: Vec<Option<usize>> = junctions.iter()
+            .flat_map(|&from| junctions.iter().map(move |&to| (from, to)))
+            .map(|(from, to)| network.shortest_path(from, to).map(|(cost, _)| cost))
+            .collect();
+        assert_eq!(simplified, costs);
+        assert_eq!(network.get_index(20), None);
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::{DistanceModel, Point};
+
+// Nodes per cell the cell size aims at, for evenly spread nodes.
+const NODES_PER_CELL: f32 = 4_f32;
+
+// A parsed node as seen by spatial lookups.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Entry {
+    pub osm_id: isize,
+    pub point: Point,
+    pub in_graph: bool,  // has an index (see `RoadNetwork::get_index`)
+    pub routable: bool,  // has an arc to or from it
+}
+
+// The parsed nodes bucketed by a square grid over latitude and longitude, so
+// lookups only look at the cells around a location instead of every node.
+// The cell size adapts to the density of the nodes.
+#[derive(Debug)]
+pub(crate) struct SpatialIndex {
+    cell_size: f32,  // in degrees (or plain units, see `Euclidean`)
+    cells: HashMap<(i64, i64), Vec<Entry>>,
+    bounds: (Point, Point),  // south-west and north-east corner of all nodes
+}
+
+impl SpatialIndex {
+    pub fn new(entries: impl IntoIterator<Item = Entry>) -> SpatialIndex {
+        let entries: Vec<Entry> = entries.into_iter().collect();
+        let (mut min, mut max) = (Point {lat: f32::MAX, lon: f32::MAX}, Point {lat: f32::MIN, lon: f32::MIN});
+        for entry in &entries {
+            min = Point {lat: min.lat.min(entry.point.lat), lon: min.lon.min(entry.point.lon)};
+            max = Point {lat: max.lat.max(entry.point.lat), lon: max.lon.max(entry.point.lon)};
+        }
+        let (lat_span, lon_span) = (max.lat - min.lat, max.lon - min.lon);
+        let per_node = NODES_PER_CELL / entries.len().max(1) as f32;
+        // nodes along a single parallel or meridian have no area to spread over
+        let cell_size = if lat_span * lon_span > 0_f32 {
+            (lat_span * lon_span * per_node).sqrt()
+        } else {
+            lat_span.max(lon_span) * per_node
+        };
+        let mut index = SpatialIndex {
+            cell_size: if cell_size.is_finite() && cell_size > 0_f32 { cell_size } else { 1_f32 },
+            cells: HashMap::new(),
+            bounds: (min, max),
+        };
+        for entry in entries {
+            index.cells.entry(index._cell(entry.point)).or_default().push(entry);
+        }
+        index
+    }
+
+    fn _cell(&self, point: Point) -> (i64, i64) {
+        ((point.lat / self.cell_size).floor() as i64, (point.lon / self.cell_size).floor() as i64)
+    }
+
+    // Up to `count` nodes accepted by `filter` closest to `location` with
+    // their distances, nearest first (ties go to the lower OSM ID). Squares
+    // around `location` grow until they hold `count` such nodes, which bounds
+    // the distance to search within (see `DistanceModel::search_box`).
+    pub fn nearest(
+        &self, location: Point, count: usize, model: &dyn DistanceModel, filter: impl Fn(&Entry) -> bool
+    ) -> Vec<(isize, f32)> {
+        let mut candidates = Vec::new();
+        let collect = |min: Point, max: Point, candidates: &mut Vec<(isize, f32)>| {
+            candidates.clear();
+            self.visit_box(min, max, |entry| if filter(entry) {
+                candidates.push((entry.osm_id, model.distance(entry.point, location)));
+            });
+            candidates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            // both halves of a box around the antimeridian may share cells
+            candidates.dedup_by_key(|candidate| candidate.0);
+        };
+        if count == 0 || self.cells.is_empty() {
+            return candidates;
+        }
+        let (south_west, north_east) = self.bounds;
+        let everything = (Point {lat: f32::MIN, lon: f32::MIN}, Point {lat: f32::MAX, lon: f32::MAX});
+        if !(location.lat.is_finite() && location.lon.is_finite()) {
+            collect(everything.0, everything.1, &mut candidates);
+            candidates.truncate(count);
+            return candidates;
+        }
+        let mut half_size = self.cell_size;
+        loop {
+            let min = Point {lat: location.lat - half_size, lon: location.lon - half_size};
+            let max = Point {lat: location.lat + half_size, lon: location.lon + half_size};
+            collect(min, max, &mut candidates);
+            let covered = min.lat <= south_west.lat && min.lon <= south_west.lon
+                && max.lat >= north_east.lat && max.lon >= north_east.lon;
+            if covered {
+                break;
+            }
+            if candidates.len() >= count {
+                let radius = candidates[count - 1].1;
+                let (min, max) = model.search_box(location, radius).unwrap_or(everything);
+                collect(min, max, &mut candidates);
+                break;
+            }
+            half_size *= 2_f32;
+        }
+        candidates.truncate(count);
+        candidates
+    }
+
+    // Visits the nodes in all cells overlapping the box spanned by `min`
+    // (south-west) and `max` (north-east), which includes some nodes just
+    // outside of it. A box with `min.lon > max.lon` wraps around the
+    // antimeridian.
+    pub fn visit_box(&self, min: Point, max: Point, mut visit: impl FnMut(&Entry)) {
+        let lon_ranges = if min.lon <= max.lon {
+            vec![(min.lon, max.lon)]
+        } else {
+            vec![(min.lon, 180_f32), (-180_f32, max.lon)]
+        };
+        for (west, east) in lon_ranges {
+            let (south_west, north_east) = (self._cell(Point {lat: min.lat, lon: west}), self._cell(Point {lat: max.lat, lon: east}));
+            // cells of huge boxes saturate, their spans mustn't overflow
+            let rows = north_east.0.saturating_sub(south_west.0).saturating_add(1) as u64;
+            let columns = north_east.1.saturating_sub(south_west.1).saturating_add(1) as u64;
+            let contains = |cell: &(i64, i64)| {
+                (south_west.0..=north_east.0).contains(&cell.0) && (south_west.1..=north_east.1).contains(&cell.1)
+            };
+            // a box larger than the data is cheaper to handle cell by cell
+            if rows.saturating_mul(columns) > self.cells.len() as u64 {
+                self.cells.iter().filter(|(cell, _)| contains(cell)).flat_map(|(_, entries)| entries).for_each(&mut visit);
+                continue;
+            }
+            for row in south_west.0..=north_east.0 {
+                for column in south_west.1..=north_east.1 {
+                    self.cells.get(&(row, column)).into_iter().flatten().for_each(&mut visit);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(points: &[(f32, f32)]) -> Vec<Entry> {
+        points.iter().enumerate()
+            .map(|(position, &(lat, lon))| Entry {osm_id: position as isize, point: Point {lat, lon}, in_graph: true, routable: true})
+            .collect()
+    }
+
+    fn visited(index: &SpatialIndex, min: Point, max: Point) -> Vec<isize> {
+        let mut osm_ids = Vec::new();
+        index.visit_box(min, max, |entry| {
+            if crate::bbox_contains(min, max, entry.point) {
+                osm_ids.push(entry.osm_id);
+            }
+        });
+        osm_ids.sort_unstable();
+        osm_ids
+    }
+
+    #[test]
+    fn boxes_find_the_same_nodes_as_a_scan() {
+        // a 20 x 20 lattice with a few outliers
+        let mut points: Vec<(f32, f32)> = (0..400).map(|i| (48_f32 + (i / 20) as f32 * 0.01, 7_f32 + (i % 20) as f32 * 0.013)).collect();
+        points.extend([(-33.9, 151.2), (48.1, 7.1), (0_f32, 0_f32)]);
+        let index = SpatialIndex::new(entries(&points));
+        let boxes = [
+            ((48.05, 7.05), (48.1, 7.1)),
+            ((47_f32, 6_f32), (49_f32, 8_f32)),
+            ((-90_f32, -180_f32), (90_f32, 180_f32)),
+            ((48.001, 7.001), (48.002, 7.002)),
+            ((48.1, 7.1), (48.1, 7.1)),
+        ];
+        for ((min_lat, min_lon), (max_lat, max_lon)) in boxes {
+            let (min, max) = (Point {lat: min_lat, lon: min_lon}, Point {lat: max_lat, lon: max_lon});
+            let expected: Vec<isize> = (0..points.len() as isize)
+                .filter(|&osm_id| crate::bbox_contains(min, max, Point {lat: points[osm_id as usize].0, lon: points[osm_id as usize].1}))
+                .collect();
+            assert_eq!(visited(&index, min, max), expected, "{:?} {:?}", min, max);
+        }
+    }
+
+    #[test]
+    fn boxes_across_the_antimeridian() {
+        let index = SpatialIndex::new(entries(&[(-17_f32, 179.9), (-17_f32, -179.9), (-17_f32, 0_f32), (-17_f32, 178_f32)]));
+        let (min, max) = (Point {lat: -18_f32, lon: 179_f32}, Point {lat: -16_f32, lon: -179_f32});
+        assert_eq!(visited(&index, min, max), vec![0, 1]);
+    }
+
+    #[test]
+    fn degenerate_layouts() {
+        for points in [vec![], vec![(1_f32, 2_f32)], vec![(1_f32, 2_f32), (1_f32, 3_f32)], vec![(5_f32, 5_f32); 3]] {
+            let index = SpatialIndex::new(entries(&points));
+            let all = visited(&index, Point {lat: -90_f32, lon: -180_f32}, Point {lat: 90_f32, lon: 180_f32});
+            assert_eq!(all, (0..points.len() as isize).collect::<Vec<_>>());
+        }
+    }
+
+    // Deterministic points spread over the globe, denser around a city, the
+    // poles and the antimeridian.
+    fn scattered() -> Vec<(f32, f32)> {
+        let mut state: u32 = 12345;
+        let mut random = move || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+        let mut points = Vec::new();
+        for _ in 0..300 {
+            points.push((random() * 180_f32 - 90_f32, random() * 360_f32 - 180_f32));
+            points.push((49_f32 + random() * 0.1, 7_f32 + random() * 0.1));
+            points.push((89.9 + random() * 0.1, random() * 360_f32 - 180_f32));
+            points.push((random() * 2_f32 - 1_f32, if random() < 0.5 { 179.9 } else { -179.9 } + random() * 0.05));
+        }
+        points
+    }
+
+    #[test]
+    fn nearest_matches_a_scan() {
+        let points = scattered();
+        let index = SpatialIndex::new(entries(&points));
+        let models: [&dyn DistanceModel; 3] = [&crate::Haversine::default(), &crate::Equirectangular::default(), &crate::Euclidean];
+        let locations = [(49.05, 7.05), (0_f32, 180_f32), (0.5, -179.99), (90_f32, 0_f32), (-60_f32, 100_f32), (1000_f32, -1000_f32)];
+        for model in models {
+            for (lat, lon) in locations {
+                let location = Point {lat, lon};
+                for count in [1, 5, 50] {
+                    let even = |entry: &Entry| entry.osm_id % 2 == 0;
+                    let mut expected: Vec<(isize, f32)> = (0..points.len() as isize)
+                        .filter(|osm_id| osm_id % 2 == 0)
+                        .map(|osm_id| {
+                            let (lat, lon) = points[osm_id as usize];
+                            (osm_id, model.distance(Point {lat, lon}, location))
+                        })
+                        .collect();
+                    expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                    expected.truncate(count);
+                    assert_eq!(index.nearest(location, count, model, even), expected, "{:?} {:?} {}", model, location, count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_of_nothing() {
+        let index = SpatialIndex::new(entries(&[(1_f32, 2_f32), (1_f32, 3_f32)]));
+        let model = crate::Haversine::default();
+        let location = Point {lat: 1_f32, lon: 2.4};
+        assert!(index.nearest(location, 0, &model, |_| true).is_empty());
+        assert!(index.nearest(location, 1, &model, |_| false).is_empty());
+        assert_eq!(index.nearest(location, 5, &model, |_| true).len(), 2);
+        assert!(SpatialIndex::new(Vec::new()).nearest(location, 1, &model, |_| true).is_empty());
+        assert_eq!(index.nearest(Point {lat: f32::NAN, lon: 0_f32}, 1, &model, |_| true).len(), 1);
+    }
+}
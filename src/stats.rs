@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{CostSummary, ParseStats, Point, RoadNetwork};
+
+// See `RoadNetwork::report`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkReport {
+    pub nodes: usize,
+    pub arcs: usize,
+    pub bbox: Option<(Point, Point)>,  // (min, max), `None` without coordinates
+    pub road_length_km: BTreeMap<String, f32>,  // by highway class
+    pub degree_histogram: BTreeMap<usize, usize>,  // nodes by number of outgoing arcs
+    pub components: usize,  // strongly connected
+    pub largest_component: usize,  // in nodes
+    pub arc_costs: CostSummary,
+    pub parse_stats: ParseStats,
+}
+
+impl RoadNetwork {
+    // Number of edges per length bucket, keyed by bucket (`0` covers
+    // `[0, bucket_meters)` and so on). Edges are undirected here: the two arcs
+    // of a bidirectional road count once. A few huge buckets far from the rest
+    // usually mean broken coordinates.
+    pub fn edge_length_histogram(&self, bucket_meters: f32) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for (_, _, length) in self._edge_lengths() {
+            *histogram.entry((length / bucket_meters) as usize).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    // The `n` longest (undirected) edges as `(osm_id_a, osm_id_b, meters)`,
+    // longest first.
+    pub fn longest_edges(&self, n: usize) -> Vec<(isize, isize, f32)> {
+        let mut edges = self._edge_lengths();
+        edges.sort_by(|a, b| b.2.total_cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+        edges.truncate(n);
+        edges
+    }
+
+    // Average of the (undirected) edge midpoints weighted by edge length,
+    // e.g. for centering a map. Plain averaging of latitudes and longitudes,
+    // so not meaningful for networks spanning the antimeridian. `None`
+    // without edges of any length.
+    pub fn weighted_center(&self) -> Option<Point> {
+        let (mut lat, mut lon, mut total) = (0_f64, 0_f64, 0_f64);
+        for (a, b, length) in self._edge_lengths() {
+            let midpoint = self.nodes[&a].interpolate(self.nodes[&b], 0.5);
+            lat += midpoint.lat as f64 * length as f64;
+            lon += midpoint.lon as f64 * length as f64;
+            total += length as f64;
+        }
+        Some(total).filter(|&total| total > 0_f64)
+            .map(|total| Point {lat: (lat / total) as f32, lon: (lon / total) as f32})
+    }
+
+    // Unweighted average of the coordinates of the graph's nodes, `None`
+    // if none has coordinates.
+    pub fn node_centroid(&self) -> Option<Point> {
+        let (mut lat, mut lon, mut count) = (0_f64, 0_f64, 0);
+        for point in self.osm_ids.iter().filter_map(|osm_id| self.nodes.get(osm_id)) {
+            lat += point.lat as f64;
+            lon += point.lon as f64;
+            count += 1;
+        }
+        Some(count).filter(|&count| count > 0)
+            .map(|count| Point {lat: (lat / count as f64) as f32, lon: (lon / count as f64) as f32})
+    }
+
+    // Everything `describe` shows, plus the degree histogram and parse
+    // stats, in one serializable struct.
+    pub fn report(&self) -> NetworkReport {
+        let components = self.strongly_connected_components();
+        let mut degree_histogram = BTreeMap::new();
+        for arcs in &self.adjacent_arcs {
+            *degree_histogram.entry(arcs.len()).or_insert(0) += 1;
+        }
+        NetworkReport {
+            nodes: self.node_count(),
+            arcs: self.arc_count(),
+            bbox: self._bbox(),
+            road_length_km: self._road_length_by_class().into_iter()
+                .map(|(class, meters)| (class.to_string(), meters / 1000_f32))
+                .collect(),
+            degree_histogram,
+            components: components.len(),
+            largest_component: components.iter().map(Vec::len).max().unwrap_or(0),
+            arc_costs: self.arc_cost_summary(),
+            parse_stats: self.parse_stats,
+        }
+    }
+
+    // `report` as JSON, for scripts.
+    pub fn stats_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.report())
+    }
+
+    // Multi-line summary for a quick look at a network: sizes, bounding box,
+    // kilometers of road per highway class, strongly connected components
+    // and the range of arc costs. The format is meant for humans and may
+    // change.
+    pub fn describe(&self) -> String {
+        let report = self.report();
+        let mut lines = vec![
+            format!("nodes: {}", report.nodes),
+            format!("arcs: {}", report.arcs),
+        ];
+        lines.push(match report.bbox {
+            Some((min, max)) => format!("bbox: {},{} to {},{}", min.lat, min.lon, max.lat, max.lon),
+            None => "bbox: none".to_string(),
+        });
+        lines.push("road length:".to_string());
+        for (class, km) in report.road_length_km {
+            lines.push(format!("  {}: {:.1} km", class, km));
+        }
+        lines.push(format!(
+            "strongly connected components: {} (largest: {} nodes)", report.components, report.largest_component
+        ));
+        let costs = report.arc_costs;
+        lines.push(format!("arc costs: {}s to {}s (mean {:.1}s, {} zero)", costs.min, costs.max, costs.mean, costs.zero_cost));
+        lines.join("\n")
+    }
+
+    fn _bbox(&self) -> Option<(Point, Point)> {
+        let mut bbox: Option<(Point, Point)> = None;
+        for point in self.osm_ids.iter().filter_map(|osm_id| self.nodes.get(osm_id)) {
+            let (min, max) = bbox.get_or_insert((*point, *point));
+            min.lat = min.lat.min(point.lat);
+            min.lon = min.lon.min(point.lon);
+            max.lat = max.lat.max(point.lat);
+            max.lon = max.lon.max(point.lon);
+        }
+        bbox
+    }
+
+    // Meters of (undirected) road per highway class, "unknown" without one.
+    fn _road_length_by_class(&self) -> BTreeMap<&str, f32> {
+        let mut seen = HashSet::new();
+        let mut lengths: BTreeMap<&str, f32> = BTreeMap::new();
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                let (a, b) = (self.osm_ids[index], self.osm_ids[arc.index]);
+                let (Some(&point_a), Some(&point_b)) = (self.nodes.get(&a), self.nodes.get(&b)) else {
+                    continue;
+                };
+                if seen.insert((a.min(b), a.max(b))) {
+                    let class = self.arc_class(arc).unwrap_or("unknown");
+                    *lengths.entry(class).or_insert(0_f32) += self.distance_model.distance(point_a, point_b);
+                }
+            }
+        }
+        lengths
+    }
+
+    fn _edge_lengths(&self) -> Vec<(isize, isize, f32)> {
+        let mut seen = HashSet::new();
+        let mut edges = Vec::new();
+        for (index, arcs) in self.adjacent_arcs.iter().enumerate() {
+            for arc in arcs {
+                let (a, b) = (self.osm_ids[index], self.osm_ids[arc.index]);
+                let edge = (a.min(b), a.max(b));
+                let (Some(&point_a), Some(&point_b)) = (self.nodes.get(&a), self.nodes.get(&b)) else {
+                    continue;
+                };
+                if seen.insert(edge) {
+                    edges.push((edge.0, edge.1, self.distance_model.distance(point_a, point_b)));
+                }
+            }
+        }
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, Point, RoadNetwork};
+
+    #[test]
+    fn huge_arc_stands_out() {
+        let mut network = fixtures::network(&[(1, 2, true), (2, 3, true)]);
+        // coordinates in the wrong hemisphere
+        network.add_node(4, Point {lat: -49_f32, lon: -7_f32});
+        network.add_oneway_arc(3, 4, 10_f32);
+        let histogram = network.edge_length_histogram(1000_f32);
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram.first_key_value(), Some((&0, &2)));
+        let (&top_bucket, &count) = histogram.last_key_value().unwrap();
+        assert_eq!(count, 1);
+        assert!(top_bucket > 10_000);
+        let longest = network.longest_edges(2);
+        assert_eq!((longest[0].0, longest[0].1), (3, 4));
+        assert_eq!(longest[0].2 as usize / 1000, top_bucket);
+        assert_eq!((longest[1].0, longest[1].1), (1, 2));
+        assert_eq!(network.longest_edges(10).len(), 3);
+    }
+
+    #[test]
+    fn describe_small_fixture() {
+        let mut network = RoadNetwork::new();
+        network.read_from_osm_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm")).unwrap();
+        let description = network.describe();
+        let lines: Vec<&str> = description.lines().collect();
+        assert_eq!(lines[..3], ["nodes: 6", "arcs: 9", "bbox: 47.995,7.845 to 48.005,7.855"]);
+        assert!(lines.contains(&"  primary: 1.1 km") && lines.contains(&"  residential: 1.1 km"));
+        // node 7 can be reached over the one-way road, but not left
+        assert!(lines.contains(&"strongly connected components: 2 (largest: 5 nodes)"));
+        assert_eq!(lines.last(), Some(&"arc costs: 40s to 44s (mean 42.2s, 0 zero)"));
+        assert!(RoadNetwork::new().describe().contains("bbox: none"));
+    }
+
+    #[test]
+    fn stats_json_of_small_fixture() {
+        let mut network = RoadNetwork::new();
+        network.read_from_osm_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm")).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&network.stats_json().unwrap()).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        for key in ["nodes", "arcs", "bbox", "road_length_km", "degree_histogram", "components", "largest_component", "arc_costs", "parse_stats"] {
+            assert!(keys.contains(&key), "{}", key);
+        }
+        assert_eq!((json["nodes"].as_u64(), json["arcs"].as_u64()), (Some(6), Some(9)));
+        assert_eq!((json["components"].as_u64(), json["largest_component"].as_u64()), (Some(2), Some(5)));
+        assert_eq!(json["bbox"][0]["lat"].as_f64().map(|lat| lat as f32), Some(47.995));
+        assert_eq!(json["bbox"][1]["lon"].as_f64().map(|lon| lon as f32), Some(7.855));
+        let primary = json["road_length_km"]["primary"].as_f64().unwrap();
+        assert!((primary - 1.1).abs() < 0.05);
+        assert_eq!((json["arc_costs"]["min"].as_u64(), json["arc_costs"]["max"].as_u64()), (Some(40), Some(44)));
+        assert_eq!(json["parse_stats"]["duplicate_refs"].as_u64(), Some(0));
+        let degrees: u64 = json["degree_histogram"].as_object().unwrap().values().map(|count| count.as_u64().unwrap()).sum();
+        assert_eq!(degrees, 6);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&RoadNetwork::new().stats_json().unwrap()).unwrap()["bbox"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn centers_of_a_symmetric_grid() {
+        let network = fixtures::grid(3);
+        let center = Point {lat: 49.001, lon: 7.001};
+        let close = |point: Point| (point.lat - center.lat).abs() < 1e-5 && (point.lon - center.lon).abs() < 1e-5;
+        assert!(close(network.node_centroid().unwrap()), "{:?}", network.node_centroid());
+        assert!(close(network.weighted_center().unwrap()), "{:?}", network.weighted_center());
+        // a long road pulls the weighted center, but not the centroid
+        let network = fixtures::network(&[(1, 2, true), (2, 3, true), (3, 20, true)]);
+        assert!((network.node_centroid().unwrap().lon - 7.0065).abs() < 1e-5);
+        assert!(network.weighted_center().unwrap().lon > 7.008);
+        assert_eq!((RoadNetwork::new().node_centroid(), RoadNetwork::new().weighted_center()), (None, None));
+        let mut isolated = RoadNetwork::new();
+        isolated.add_node(1, center);
+        isolated.get_or_create_index(1);
+        assert_eq!((isolated.node_centroid(), isolated.weighted_center()), (Some(center), None));
+    }
+}
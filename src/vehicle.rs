@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{RoadNetwork, WayTags};
+
+const FOOT: f32 = 0.3048;  // in meters
+
+// Size and weight of a vehicle, or the limits of a road for them. `None`
+// means unknown for a vehicle and no restriction for a road.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Dimensions {
+    pub weight: Option<f32>,  // in tonnes
+    pub height: Option<f32>,  // in meters
+    pub width: Option<f32>,  // in meters
+}
+
+impl Dimensions {
+    // From `maxweight`, `maxheight` and `maxwidth`; `None` if the way has
+    // no usable limit.
+    pub(crate) fn from_tags(tags: &WayTags) -> Option<Dimensions> {
+        let limits = Dimensions {
+            weight: tags.maxweight.as_deref().and_then(parse_weight),
+            height: tags.maxheight.as_deref().and_then(parse_length),
+            width: tags.maxwidth.as_deref().and_then(parse_length),
+        };
+        Some(limits).filter(|limits| *limits != Dimensions::default())
+    }
+
+    // Whether a vehicle of these dimensions may use a road with `limits`.
+    pub fn fits(&self, limits: &Dimensions) -> bool {
+        let within = |value: Option<f32>, limit: Option<f32>| match (value, limit) {
+            (Some(value), Some(limit)) => value <= limit,
+            _ => true,
+        };
+        within(self.weight, limits.weight) && within(self.height, limits.height) && within(self.width, limits.width)
+    }
+}
+
+// Splits "3.5 t" into the number and the unit ("t"), `None` for values that
+// don't start with a positive number (e.g. "none", "default").
+fn split_unit(value: &str) -> Option<(f32, &str)> {
+    let value = value.trim();
+    let end = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let number = value[..end].parse::<f32>().ok().filter(|number| *number > 0_f32)?;
+    Some((number, value[end..].trim()))
+}
+
+// Tonnes by default, "t" and "kg" are understood.
+fn parse_weight(value: &str) -> Option<f32> {
+    match split_unit(value)? {
+        (tonnes, "" | "t") => Some(tonnes),
+        (kilograms, "kg") => Some(kilograms / 1000_f32),
+        _ => None,
+    }
+}
+
+// Meters by default, also "m" and feet/inches such as `12'6"`.
+fn parse_length(value: &str) -> Option<f32> {
+    match split_unit(value)? {
+        (meters, "" | "m") => Some(meters),
+        (feet, "'") => Some(feet * FOOT),
+        (feet, inches) => {
+            let inches = inches.strip_prefix('\'')?.trim().strip_suffix('"')?;
+            let inches = inches.trim().parse::<f32>().ok()?;
+            Some((feet + inches / 12_f32) * FOOT)
+        }
+    }
+}
+
+impl RoadNetwork {
+    // Shortest path for a vehicle of the given dimensions, avoiding arcs from
+    // ways with a lower `maxweight`, `maxheight` or `maxwidth`.
+    pub fn route_respecting_max_weight(
+        &self, from: isize, to: isize, vehicle: Dimensions
+    ) -> Option<(usize, Vec<isize>)> {
+        let source = self.get_index(from)?;
+        let target = self.get_index(to)?;
+        let search = self._dijkstra(source, Some(target), None, None, |_, _, arc| {
+            let limits = arc.way.and_then(|way| self.dimension_limits.get(&way));
+            Some(arc.cost).filter(|_| limits.is_none_or(|limits| vehicle.fits(limits)))
+        }).ok()?;
+        let path = search.path_to(target)?;
+        Some((search.costs[target], self._to_osm_ids(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn truck(weight: f32) -> Dimensions {
+        Dimensions {weight: Some(weight), ..Dimensions::default()}
+    }
+
+    #[test]
+    fn heavy_vehicles_detour_around_weight_limits() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.01), (3, 49.005, 7.005)],
+            &[
+                (10, &[1, 2], &[("highway", "primary"), ("maxweight", "3.5")]),
+                (11, &[1, 3, 2], &[("highway", "primary")]),
+            ],
+        );
+        let network = fixtures::parse(&xml);
+        assert_eq!(network.route_respecting_max_weight(1, 2, truck(3_f32)).unwrap().1, vec![1, 2]);
+        assert_eq!(network.route_respecting_max_weight(1, 2, truck(7.5)).unwrap().1, vec![1, 3, 2]);
+        // no known weight means no restriction
+        assert_eq!(network.route_respecting_max_weight(1, 2, Dimensions::default()).unwrap().1, vec![1, 2]);
+    }
+
+    #[test]
+    fn limits_stay_on_their_way() {
+        // a limited bridge and an unrestricted road side by side between the
+        // same two nodes
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.01)],
+            &[
+                (10, &[1, 2], &[("highway", "primary"), ("maxweight", "3.5")]),
+                (11, &[1, 2], &[("highway", "residential")]),
+            ],
+        );
+        let network = fixtures::parse(&xml);
+        let (cost, path) = network.route_respecting_max_weight(1, 2, truck(7.5)).unwrap();
+        assert_eq!(path, vec![1, 2]);
+        let residential = network.adjacent_arcs()[network.get_index(1).unwrap()].iter()
+            .find(|arc| arc.way == Some(11)).unwrap();
+        assert_eq!(cost, residential.cost);
+        let (cost, _) = network.route_respecting_max_weight(1, 2, truck(3_f32)).unwrap();
+        assert!(cost < residential.cost);
+    }
+
+    #[test]
+    fn limits_survive_reversal_and_simplification() {
+        let xml = fixtures::osm_xml(
+            &[(1, 49_f32, 7_f32), (2, 49_f32, 7.001), (3, 49_f32, 7.002), (4, 49_f32, 7.003), (5, 49.001, 7.0015)],
+            &[
+                (10, &[1, 2], &[("highway", "primary")]),
+                (11, &[2, 3], &[("highway", "primary"), ("maxheight", "12'6&quot;")]),
+                (12, &[3, 4], &[("highway", "primary")]),
+                (13, &[1, 5, 4], &[("highway", "residential")]),
+            ],
+        );
+        let network = fixtures::parse(&xml);
+        let tall = Dimensions {height: Some(4_f32), ..Dimensions::default()};
+        assert_eq!(network.route_respecting_max_weight(1, 4, Dimensions::default()).unwrap().1, vec![1, 2, 3, 4]);
+        assert_eq!(network.route_respecting_max_weight(1, 4, tall).unwrap().1, vec![1, 5, 4]);
+        assert_eq!(network.reverse().route_respecting_max_weight(4, 1, tall).unwrap().1, vec![4, 5, 1]);
+        // the shape node 5 goes, the ends of the limited arc stay
+        let mut simplified = fixtures::parse(&xml);
+        simplified.simplify_geometry(f32::INFINITY);
+        assert_eq!(simplified.route_respecting_max_weight(1, 4, Dimensions::default()).unwrap().1, vec![1, 2, 3, 4]);
+        assert_eq!(simplified.route_respecting_max_weight(1, 4, tall).unwrap().1, vec![1, 4]);
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_weight("3.5"), Some(3.5));
+        assert_eq!(parse_weight("7500 kg"), Some(7.5));
+        assert_eq!(parse_weight("none"), None);
+        assert_eq!(parse_length("4.2 m"), Some(4.2));
+        assert!((parse_length("12'6\"").unwrap() - 3.81).abs() < 1e-3);
+        assert!((parse_length("13'").unwrap() - 3.9624).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,108 @@
+// Runs the binary on the fixture in `tests/data`.
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm");
+
+fn stdout(output: Output) -> String {
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn run(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_hello-rust")).args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+// A file name in the temp directory that other test processes don't use.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("hello-rust-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn stats_dump_lists_neighbors() {
+    let dump = temp_path("dump.txt");
+    let text = stdout(run(&["stats", FIXTURE, "--dump", dump.to_str().unwrap()]));
+    assert!(text.starts_with("nodes: 6\narcs: 9\n"));
+    assert!(!text.contains("(44s)"));
+    let lines = fs::read_to_string(&dump).unwrap();
+    fs::remove_file(&dump).unwrap();
+    assert!(lines.lines().any(|line| line == "2: 1 (44s), 3 (44s), 4 (40s)"));
+    assert_eq!(lines.lines().count(), 6);
+}
+
+#[test]
+fn stats_prints_summary() {
+    let text = stdout(run(&["stats", FIXTURE]));
+    assert_eq!(text.lines().count(), 4);
+    assert!(text.contains("CostSummary { min: 40, max: 44"));
+    assert!(text.contains("ParseStats { duplicate_refs: 0, rejected_long_arcs: 0"));
+    let json: serde_json::Value = serde_json::from_str(&stdout(run(&["stats", FIXTURE, "--format", "json"]))).unwrap();
+    assert!(json.is_object());
+}
+
+#[test]
+fn route_between_nodes_and_coordinates() {
+    let text = stdout(run(&["route", FIXTURE, "--from", "1", "--to", "5"]));
+    assert_eq!(text, "cost: 124s\npath: [1, 2, 4, 5]\nways: [100, 101]\n");
+    let text = stdout(run(&["route", FIXTURE, "--from", "47.995,7.845", "--to", "48.005,7.85"]));
+    assert!(text.starts_with("cost: 124s\npath: [1, 2, 4, 5]\nways: [100, 101]\n"));
+    let text = stdout(run(&["route", FIXTURE, "--from-coord", "47.9951,7.845", "--to-coord", "48.005,7.85"]));
+    assert_eq!(text, "cost: 124s\npath: [1, 2, 4, 5]\nways: [100, 101]\nsnapped: 11m, 0m\n");
+    // node 7 can only be reached through the one-way street from 5
+    let output = Command::new(env!("CARGO_BIN_EXE_hello-rust"))
+        .args(["route", FIXTURE, "--from", "7", "--to", "1"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn export_formats() {
+    let formats = [
+        ("geojson", "{\"type\":\"FeatureCollection\""), ("csv", "from,to,cost,"), ("dimacs", "c "), ("graphml", "<?xml"),
+        ("wkt", "LINESTRING ("), ("osm", "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm "),
+    ];
+    for (format, start) in formats {
+        let out = temp_path(&format!("export.{}", format));
+        run(&["export", FIXTURE, "--format", format, "--out", out.to_str().unwrap()]);
+        let exported = fs::read_to_string(&out).unwrap();
+        fs::remove_file(&out).unwrap();
+        assert!(exported.starts_with(start), "{}: {}", format, exported);
+    }
+}
+
+#[test]
+fn convert_to_cache_keeps_stats() {
+    let cache = temp_path("small.cache");
+    run(&["convert", FIXTURE, "--to-cache", cache.to_str().unwrap()]);
+    let from_cache = stdout(run(&["stats", cache.to_str().unwrap()]));
+    fs::remove_file(&cache).unwrap();
+    assert_eq!(from_cache, stdout(run(&["stats", FIXTURE])));
+}
+
+#[test]
+fn max_speed_override_slows_routes() {
+    let text = stdout(run(&["route", FIXTURE, "--from", "1", "--to", "5", "--max-speed-override", "10"]));
+    let cost: usize = text.lines().next().unwrap().trim_start_matches("cost: ").trim_end_matches('s').parse().unwrap();
+    assert!(cost > 124);
+}
+
+#[test]
+fn repl_answers_until_quit_or_eof() {
+    for input in ["stats\nbogus\nroute 1 5\nquit\nstats\n", "stats\nbogus\nroute 1 5"] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_hello-rust"))
+            .args(["--repl", FIXTURE])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let text = stdout(output);
+        assert_eq!(text.matches("nodes: 6, arcs: 9").count(), 1, "{}", text);
+        assert_eq!(text.matches("commands: ").count(), 2, "{}", text);
+        assert!(text.contains("cost: 124s over 4 nodes\npath: [1, 2, 4, 5]"), "{}", text);
+    }
+}
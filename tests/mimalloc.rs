@@ -0,0 +1,29 @@
+// Only built with `--features mimalloc`: the binary (and this test) run on
+// mimalloc and must parse exactly as with the system allocator.
+#![cfg(feature = "mimalloc")]
+
+use std::process::Command;
+
+use hello_rust::RoadNetwork;
+
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/small.osm");
+
+#[test]
+fn binary_parses_fixture_like_the_library() {
+    let mut road_network = RoadNetwork::new();
+    road_network.read_from_osm_file(FIXTURE).unwrap();
+    let expected = format!(
+        "nodes: {}\narcs: {}\n{:?}\n{:?}\n",
+        road_network.node_count(),
+        road_network.arc_count(),
+        road_network.arc_cost_summary(),
+        road_network.parse_stats(),
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_hello-rust")).args(["stats", FIXTURE]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    assert_eq!(road_network.node_count(), 6);
+}